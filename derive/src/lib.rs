@@ -0,0 +1,153 @@
+//! The derive macros of [rcu_cell](https://docs.rs/rcu_cell), use them
+//! through its `derive` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Ident};
+
+/// Derive the per-field rcu view of a config struct
+///
+/// For a struct `Config` it generates a `ConfigRcuFields` struct that holds
+/// every field in a `RcuCellNonEmpty` of its own, with the same name and
+/// visibility, so each field is read and hot reloaded without cloning or
+/// locking the others. It also generates a `ConfigRcuField` enum with one
+/// variant per field that carries a new value of the field.
+///
+/// - `ConfigRcuFields::new(config)` and `From<Config>` split the value
+/// - `snapshot()` clones the fields back into a `Config`, the fields are read
+///   one by one, so it is not atomic against the concurrent writers
+/// - `update_field(ConfigRcuField::Port(8080))` writes a single field
+///
+/// Only the structs with named fields and without generics are supported,
+/// and the fields must be `Clone`.
+///
+/// ```
+/// use rcu_cell::RcuFields;
+///
+/// #[derive(RcuFields)]
+/// struct Config {
+///     port: u16,
+///     name: String,
+/// }
+///
+/// let fields = ConfigRcuFields::new(Config {
+///     port: 80,
+///     name: "rcu".into(),
+/// });
+/// fields.update_field(ConfigRcuField::Port(8080));
+/// assert_eq!(*fields.port.read(), 8080);
+/// let config = fields.snapshot();
+/// assert_eq!((config.port, config.name.as_str()), (8080, "rcu"));
+/// ```
+#[proc_macro_derive(RcuFields)]
+pub fn derive_rcu_fields(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    rcu_fields(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn rcu_fields(input: DeriveInput) -> syn::Result<TokenStream2> {
+    if !input.generics.params.is_empty() || input.generics.where_clause.is_some() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "RcuFields doesn't support the generic structs",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &data.fields,
+                    "RcuFields only supports the structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                "RcuFields only supports the structs",
+            ))
+        }
+    };
+
+    let vis = &input.vis;
+    let name = &input.ident;
+    let cells = format_ident!("{}RcuFields", name);
+    let field = format_ident!("{}RcuField", name);
+    let cells_doc = format!("The per-field rcu view of `{name}`, see `RcuFields`");
+    let field_doc = format!("A new value of a field of `{name}`, see `{cells}::update_field`");
+
+    let names: Vec<_> = fields.iter().map(|f| f.ident.as_ref().unwrap()).collect();
+    let tys: Vec<_> = fields.iter().map(|f| &f.ty).collect();
+    let vises: Vec<_> = fields.iter().map(|f| &f.vis).collect();
+    let variants: Vec<_> = names.iter().map(|n| variant_name(n)).collect();
+
+    // the generated items are only partly used by most of the users
+    Ok(quote! {
+        #[doc = #cells_doc]
+        #[allow(dead_code)]
+        #vis struct #cells {
+            #(#vises #names: ::rcu_cell::RcuCellNonEmpty<#tys>,)*
+        }
+
+        #[doc = #field_doc]
+        #[allow(dead_code)]
+        #vis enum #field {
+            #(#variants(#tys),)*
+        }
+
+        #[allow(dead_code)]
+        impl #cells {
+            /// split the value into the rcu cells of its fields
+            #vis fn new(value: #name) -> Self {
+                #cells {
+                    #(#names: ::rcu_cell::RcuCellNonEmpty::new(value.#names),)*
+                }
+            }
+
+            /// clone the current values of the fields into a new value,
+            /// the fields are read one by one
+            #vis fn snapshot(&self) -> #name {
+                #name {
+                    #(#names: ::core::clone::Clone::clone(&*self.#names.read()),)*
+                }
+            }
+
+            /// write a new value to its field
+            #vis fn update_field(&self, field: #field) {
+                match field {
+                    #(#field::#variants(value) => {
+                        self.#names.write(value);
+                    })*
+                }
+            }
+        }
+
+        impl ::core::convert::From<#name> for #cells {
+            fn from(value: #name) -> Self {
+                #cells::new(value)
+            }
+        }
+    })
+}
+
+// the UpperCamelCase variant of a snake_case field name
+fn variant_name(ident: &Ident) -> Ident {
+    let field = ident.to_string();
+    let field = field.trim_start_matches("r#");
+    let mut name = String::with_capacity(field.len());
+    for word in field.split('_').filter(|w| !w.is_empty()) {
+        let mut chars = word.chars();
+        if let Some(c) = chars.next() {
+            name.extend(c.to_uppercase());
+            name.push_str(chars.as_str());
+        }
+    }
+    if name.is_empty() {
+        name.push_str("Field");
+    }
+    Ident::new(&name, ident.span())
+}
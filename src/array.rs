@@ -0,0 +1,84 @@
+use alloc::sync::Arc;
+
+use crate::{RcuCell, RcuGroup};
+
+/// A fixed array of `N` rcu slots that can be read as a consistent snapshot.
+///
+/// It's an `RcuGroup` whose length is known at compile time, the slots are
+/// read without any lock and `snapshot` returns all the `N` values as they
+/// were at a single point in time. Writers to the slots are serialized by
+/// the group's sequence counter, see `RcuGroup`.
+#[derive(Debug)]
+pub struct RcuArray<T, const N: usize> {
+    group: RcuGroup<T>,
+}
+
+impl<T, const N: usize> Default for RcuArray<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> From<[T; N]> for RcuArray<T, N> {
+    fn from(values: [T; N]) -> Self {
+        RcuArray {
+            group: values.into_iter().map(RcuCell::new).collect(),
+        }
+    }
+}
+
+impl<T, const N: usize> RcuArray<T, N> {
+    /// create an array of empty slots
+    pub fn new() -> Self {
+        RcuArray {
+            group: RcuGroup::new(N),
+        }
+    }
+
+    /// the number of slots
+    #[inline]
+    pub const fn len(&self) -> usize {
+        N
+    }
+
+    /// check if the array has no slots
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    /// read out the value of a slot, panics if `index` is out of range
+    #[inline]
+    pub fn read(&self, index: usize) -> Option<Arc<T>> {
+        self.group.read(index)
+    }
+
+    /// write a value to a slot and return the old value,
+    /// panics if `index` is out of range
+    #[inline]
+    pub fn write(&self, index: usize, data: impl Into<Arc<T>>) -> Option<Arc<T>> {
+        self.group.write(index, data)
+    }
+
+    /// take a consistent snapshot of all the slots
+    pub fn snapshot(&self) -> [Option<Arc<T>>; N] {
+        let values = self.group.snapshot();
+        values
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("rcu array has exactly N slots"))
+    }
+
+    /// Atomicly update several slots with a closure and return the old
+    /// snapshot, see `RcuGroup::update`
+    pub fn update<F>(&self, f: F) -> [Option<Arc<T>>; N]
+    where
+        F: FnOnce(&mut [Option<Arc<T>>; N]),
+    {
+        let old = self.group.update(|values| {
+            let values = values.try_into().expect("rcu array has exactly N slots");
+            f(values)
+        });
+        old.try_into()
+            .unwrap_or_else(|_| unreachable!("rcu array has exactly N slots"))
+    }
+}
@@ -0,0 +1,80 @@
+// The backoff of the spinning readers and writers.
+//
+// It's the `crossbeam_utils::Backoff`, except that with the `may` feature
+// a snooze inside a coroutine yields to the coroutine scheduler, so the
+// thread can run the other coroutines, like the one holding the lock.
+// Under `--cfg loom` a snooze yields to the loom scheduler instead and the
+// backoff never completes, so the spinning threads are never parked.
+
+#[cfg(feature = "may")]
+use core::cell::Cell;
+
+// the same number of snoozes as `crossbeam_utils::Backoff` before completion
+#[cfg(feature = "may")]
+#[cfg_attr(loom, allow(dead_code))]
+const YIELD_LIMIT: u32 = 10;
+
+#[cfg_attr(loom, allow(dead_code))]
+pub(crate) struct Backoff {
+    inner: crossbeam_utils::Backoff,
+    // the snoozes that yielded to the coroutine scheduler
+    #[cfg(feature = "may")]
+    yields: Cell<u32>,
+}
+
+impl Backoff {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Backoff {
+            inner: crossbeam_utils::Backoff::new(),
+            #[cfg(feature = "may")]
+            yields: Cell::new(0),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn snooze(&self) {
+        #[cfg(loom)]
+        loom::thread::yield_now();
+        #[cfg(not(loom))]
+        {
+            #[cfg(feature = "may")]
+            if may::coroutine::is_coroutine() {
+                self.yields.set(self.yields.get().saturating_add(1));
+                return may::coroutine::yield_now();
+            }
+            self.inner.snooze();
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_completed(&self) -> bool {
+        #[cfg(loom)]
+        {
+            false
+        }
+        #[cfg(not(loom))]
+        {
+            #[cfg(feature = "may")]
+            if self.yields.get() > YIELD_LIMIT {
+                return true;
+            }
+            self.inner.is_completed()
+        }
+    }
+}
+
+// whether the current thread is running a coroutine,
+// a coroutine must never block the thread it's running on
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn in_coroutine() -> bool {
+    #[cfg(feature = "may")]
+    {
+        may::coroutine::is_coroutine()
+    }
+    #[cfg(not(feature = "may"))]
+    {
+        false
+    }
+}
@@ -0,0 +1,70 @@
+use alloc::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::RcuCell;
+
+/// RCU cell of a `bytes::Bytes` payload, an empty cell reads as empty bytes
+///
+/// `Bytes` is already cheaply cloneable, so the readers clone it straight
+/// out of the cell and only touch the refcount of the payload itself,
+/// never the one of the box that holds it in the cell.
+#[derive(Debug, Default)]
+pub struct RcuBytesCell {
+    cell: RcuCell<Bytes>,
+}
+
+impl RcuBytesCell {
+    /// create rcu bytes cell from the payload
+    #[inline]
+    pub fn new(data: impl Into<Bytes>) -> Self {
+        RcuBytesCell {
+            cell: RcuCell::new(data.into()),
+        }
+    }
+
+    /// read out the payload
+    #[inline]
+    pub fn read(&self) -> Bytes {
+        self.cell.with_ref(|v| v.cloned().unwrap_or_default())
+    }
+
+    /// write a new payload to the rcu bytes cell and return the old one
+    #[inline]
+    pub fn write(&self, data: impl Into<Bytes>) -> Bytes {
+        into_bytes(self.cell.write(data.into()))
+    }
+
+    /// Stores the `new` payload into the cell if the current payload is the
+    /// same as `current`, two `Bytes` are the same if they are the same view
+    /// of the same memory.
+    ///
+    /// Return the old payload on success, otherwise give back `new`.
+    pub fn compare_exchange(&self, current: &Bytes, new: Bytes) -> Result<Bytes, Bytes> {
+        let mut new = Some(new);
+        let ret = self.cell.update_if(|old| {
+            let old = old.map(|v| v.as_ref()).cloned().unwrap_or_default();
+            if old.as_ptr() == current.as_ptr() && old.len() == current.len() {
+                Ok(new.take().map(Arc::new))
+            } else {
+                Err(())
+            }
+        });
+        match ret {
+            Ok(old) => Ok(into_bytes(old)),
+            Err(()) => Err(new.take().unwrap_or_default()),
+        }
+    }
+}
+
+impl From<Bytes> for RcuBytesCell {
+    fn from(data: Bytes) -> Self {
+        Self::new(data)
+    }
+}
+
+// the box in the cell may still be shared by the in-flight readers
+#[inline]
+fn into_bytes(v: Option<Arc<Bytes>>) -> Bytes {
+    v.map(Arc::unwrap_or_clone).unwrap_or_default()
+}
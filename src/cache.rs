@@ -0,0 +1,52 @@
+use alloc::sync::Arc;
+use core::ops::Deref;
+
+use crate::RcuCell;
+
+/// A cache of the value of an rcu cell for repeated reads.
+///
+/// `load` only checks the inner pointer of the cell, and reads the cell
+/// again only when the value actually changed. Event-loop code that reads
+/// the same cell over and over between rare updates pays no refcount
+/// traffic for it.
+///
+/// The cache holds the last seen value, so it's not released until the
+/// cache observes a new value or is dropped.
+#[derive(Debug, Clone)]
+pub struct Cache<C, T> {
+    cell: C,
+    value: Option<Arc<T>>,
+}
+
+impl<C, T> Cache<C, T>
+where
+    C: Deref<Target = RcuCell<T>>,
+{
+    /// create a cache of the cell, `cell` can be `&RcuCell<T>`,
+    /// `Arc<RcuCell<T>>` or anything that derefs to the cell
+    pub fn new(cell: C) -> Self {
+        let value = cell.read();
+        Cache { cell, value }
+    }
+
+    /// get the cell that is cached
+    #[inline]
+    pub fn cell(&self) -> &RcuCell<T> {
+        &self.cell
+    }
+
+    /// load the value of the cell, only read the cell if it has changed
+    #[inline]
+    pub fn load(&mut self) -> &Option<Arc<T>> {
+        // the cached Arc keeps the allocation, so the address can't be
+        // reused by a different value while it's cached
+        let unchanged = match &self.value {
+            Some(v) => self.cell.arc_eq(v),
+            None => self.cell.is_none(),
+        };
+        if !unchanged {
+            self.cell.read_into(&mut self.value);
+        }
+        &self.value
+    }
+}
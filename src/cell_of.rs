@@ -0,0 +1,199 @@
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::sync::atomic::Ordering;
+
+use crate::link::{Credit, LinkWrapper, UpdateLock, PTR_ALIGN};
+use crate::sync::{const_fn, fence};
+use crate::ArcPointer;
+
+/// A reference counted pointer that can be stored in `RcuCellOf`
+///
+/// # Safety
+///
+/// `into_raw` and `from_raw` must round-trip the pointer, a null pointer
+/// stands for no value, and a clone must share the pointee and hold a
+/// reference count of it until it's dropped. On 64-bit targets without the
+/// `two-word` feature a non-null pointer must be aligned to 8 bytes, the
+/// packed link keeps its flags in the low bits. It's checked on every
+/// write, a misaligned pointer panics.
+pub unsafe trait RcuPointer<T>: ArcPointer<T> + Clone {}
+
+unsafe impl<T> RcuPointer<T> for Option<alloc::sync::Arc<T>> {}
+
+#[cfg(feature = "triomphe")]
+impl<T> ArcPointer<T> for Option<triomphe::Arc<T>> {
+    fn as_ptr(&self) -> *const T {
+        match self {
+            Some(v) => v.as_ptr(),
+            None => core::ptr::null(),
+        }
+    }
+
+    fn into_raw(self) -> *const T {
+        match self {
+            Some(v) => triomphe::Arc::into_raw(v),
+            None => core::ptr::null(),
+        }
+    }
+
+    unsafe fn from_raw(ptr: *const T) -> Self {
+        (!ptr.is_null()).then(|| triomphe::Arc::from_raw(ptr))
+    }
+}
+
+#[cfg(feature = "triomphe")]
+unsafe impl<T> RcuPointer<T> for Option<triomphe::Arc<T>> {}
+
+// the pointer of `data`, the packed link would silently corrupt the flags
+// in its low bits with a misaligned one
+#[inline]
+fn pointer_to_ptr<T, P: RcuPointer<T>>(data: P) -> *const T {
+    let ptr = data.into_raw();
+    assert!(
+        ptr.cast::<u8>().align_offset(PTR_ALIGN) == 0,
+        "the RcuPointer is not aligned to {PTR_ALIGN} bytes"
+    );
+    ptr
+}
+
+#[inline]
+fn ptr_to_pointer<T, P: RcuPointer<T>>(ptr: *const T) -> P {
+    unsafe { P::from_raw(ptr) }
+}
+
+// credit the counts of the laggard readers to the swapped out pointer,
+// see `Credit`
+struct PointerCredit<P>(PhantomData<P>);
+
+impl<T, P: RcuPointer<T>> Credit<T> for PointerCredit<P> {
+    #[inline]
+    unsafe fn credit(&self, ptr: *const T, n: isize) {
+        let p = ManuallyDrop::new(ptr_to_pointer::<T, P>(ptr));
+        for _ in 0..n {
+            let _ = ManuallyDrop::new(P::clone(&p));
+        }
+        for _ in n..0 {
+            drop(ptr_to_pointer::<T, P>(ptr));
+        }
+    }
+}
+
+/// RCU cell that holds any reference counted pointer, like the
+/// `Option<triomphe::Arc<T>>` with the `triomphe` feature
+///
+/// It's the `RcuCell` with the pointer type as a parameter, the values are
+/// read and written as the `P` pointers, `None` stands for no value.
+pub struct RcuCellOf<T, P: RcuPointer<T>> {
+    link: LinkWrapper<T>,
+    _marker: PhantomData<P>,
+}
+
+impl<T, P: RcuPointer<T>> fmt::Debug for RcuCellOf<T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RcuCellOf")
+            .field("state", &self.link.state())
+            .finish()
+    }
+}
+
+unsafe impl<T: Send, P: RcuPointer<T> + Send> Send for RcuCellOf<T, P> {}
+unsafe impl<T: Send + Sync, P: RcuPointer<T> + Send + Sync> Sync for RcuCellOf<T, P> {}
+
+impl<T, P: RcuPointer<T>> Drop for RcuCellOf<T, P> {
+    fn drop(&mut self) {
+        let ptr = self.link.get_ref();
+        let _ = ptr_to_pointer::<T, P>(ptr);
+    }
+}
+
+impl<T, P: RcuPointer<T>> Default for RcuCellOf<T, P> {
+    fn default() -> Self {
+        RcuCellOf::none()
+    }
+}
+
+impl<T, P: RcuPointer<T>> From<P> for RcuCellOf<T, P> {
+    fn from(data: P) -> Self {
+        RcuCellOf {
+            link: LinkWrapper::new(pointer_to_ptr(data)),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, P: RcuPointer<T>> RcuCellOf<T, P> {
+    const_fn! {
+        /// create an empty rcu cell instance
+        #[inline]
+        pub const fn none() -> Self {
+            RcuCellOf {
+                link: LinkWrapper::none(),
+                _marker: PhantomData,
+            }
+        }
+    }
+
+    /// convert the rcu cell to the inner pointer
+    #[inline]
+    pub fn into_pointer(self) -> P {
+        let ptr = self.link.get_ref();
+        let _ = ManuallyDrop::new(self);
+        ptr_to_pointer(ptr)
+    }
+
+    /// check if the rcu cell is empty
+    #[inline]
+    pub fn is_none(&self) -> bool {
+        self.link.is_none()
+    }
+
+    /// read out the inner pointer
+    #[inline]
+    pub fn read(&self) -> P {
+        let ptr = self.link.inc_ref();
+        let v = ManuallyDrop::new(ptr_to_pointer::<T, P>(ptr));
+        let cloned = P::clone(&v);
+        if self.link.dec_ref(ptr) {
+            // a writer has credited our count to the pointer
+            drop(ptr_to_pointer::<T, P>(ptr));
+        }
+        fence(Ordering::Acquire);
+        cloned
+    }
+
+    /// write a pointer to the rcu cell and return the old one
+    #[inline]
+    pub fn set(&self, data: P) -> P {
+        let (old_ptr, _gen) = self
+            .link
+            .update(pointer_to_ptr(data), PointerCredit::<P>(PhantomData));
+        ptr_to_pointer(old_ptr)
+    }
+
+    /// take the value from the rcu cell, leave the rcu cell empty
+    #[inline]
+    pub fn take(&self) -> P {
+        self.set(ptr_to_pointer(core::ptr::null()))
+    }
+
+    /// Atomicly update the pointer with a closure and return the old one,
+    /// see `RcuCell::update`
+    pub fn update<F>(&self, f: F) -> P
+    where
+        F: FnOnce(P) -> P,
+    {
+        let lock = UpdateLock::new(&self.link);
+        let ptr = lock.ptr();
+        let old_value = ManuallyDrop::new(ptr_to_pointer::<T, P>(ptr));
+        let new_ptr = pointer_to_ptr(f((*old_value).clone()));
+        lock.unlock_update(new_ptr, PointerCredit::<P>(PhantomData));
+        ManuallyDrop::into_inner(old_value)
+    }
+
+    /// the version of the rcu cell, see `RcuCell::version`
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.link.gen()
+    }
+}
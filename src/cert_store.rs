@@ -0,0 +1,105 @@
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::RcuCell;
+
+/// The source of the certificates of a `RcuCertStore`
+///
+/// It reads and parses the certificate chain and the private key into
+/// whatever the TLS library hands to the acceptor, like a
+/// `rustls::sign::CertifiedKey`, so the store doesn't depend on any of them.
+pub trait CertSource {
+    /// the parsed certificate and key
+    type Cert;
+    /// the error of reading or parsing them
+    type Error;
+
+    /// read and parse the certificate and key
+    fn load(&self) -> Result<Self::Cert, Self::Error>;
+}
+
+/// Statistics of the reloads of a `RcuCertStore`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReloadStats {
+    /// the number of certificates published after the initial one
+    pub reloads: usize,
+    /// the number of reloads that failed and kept the old certificate
+    pub failures: usize,
+}
+
+/// A store of the certificate that a TLS server hands out, hot reloaded
+/// without blocking the handshakes.
+///
+/// The acceptor takes the `current` certificate on every handshake, while
+/// a watcher thread calls `reload` when the files change. A failed reload
+/// keeps serving the old certificate, so a half written file never takes
+/// the server down. See `examples/tls_reload.rs` for the whole pattern.
+#[derive(Debug)]
+pub struct RcuCertStore<C> {
+    cell: RcuCell<C>,
+    reloads: AtomicUsize,
+    failures: AtomicUsize,
+}
+
+impl<C> RcuCertStore<C> {
+    /// create the store with the initial certificate
+    #[inline]
+    pub fn new(cert: impl Into<Arc<C>>) -> Self {
+        RcuCertStore {
+            cell: RcuCell::from(cert.into()),
+            reloads: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+        }
+    }
+
+    /// create the store with the certificate loaded from `source`
+    pub fn load<S>(source: &S) -> Result<Self, S::Error>
+    where
+        S: CertSource<Cert = C>,
+    {
+        source.load().map(Self::new)
+    }
+
+    /// get the current certificate, call it for every handshake
+    #[inline]
+    pub fn current(&self) -> Arc<C> {
+        self.cell.read().expect("cert store is never empty")
+    }
+
+    /// publish a new certificate and return the old one
+    pub fn store(&self, cert: impl Into<Arc<C>>) -> Arc<C> {
+        let old = self.cell.write(cert).expect("cert store is never empty");
+        self.reloads.fetch_add(1, Ordering::Relaxed);
+        old
+    }
+
+    /// Load the certificate from `source` again and publish it, return the
+    /// old one. On error the old certificate is still served.
+    pub fn reload<S>(&self, source: &S) -> Result<Arc<C>, S::Error>
+    where
+        S: CertSource<Cert = C>,
+    {
+        match source.load() {
+            Ok(cert) => Ok(self.store(cert)),
+            Err(e) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                Err(e)
+            }
+        }
+    }
+
+    /// the version of the current certificate, it's bumped on every reload
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.cell.version()
+    }
+
+    /// the statistics of the reloads
+    #[inline]
+    pub fn stats(&self) -> ReloadStats {
+        ReloadStats {
+            reloads: self.reloads.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+        }
+    }
+}
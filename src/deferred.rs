@@ -0,0 +1,113 @@
+use alloc::boxed::Box;
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+// the header of a deferred value with the function that drops it
+struct Header {
+    next: *mut Header,
+    drop: unsafe fn(*mut Header),
+}
+
+#[repr(C)]
+struct Node<T> {
+    header: Header,
+    value: T,
+}
+
+unsafe fn drop_node<T>(node: *mut Header) {
+    drop(unsafe { Box::from_raw(node.cast::<Node<T>>()) });
+}
+
+/// A queue of the values whose drop is deferred
+///
+/// A writer pushes the old value with `defer` instead of dropping it, the
+/// push is a single allocation and a CAS, and `collect` drops all the
+/// queued values later in a batch, on a thread that doesn't mind the
+/// latency. The global collector is used by `RcuCell::write_deferred` and
+/// flushed by `rcu_cell::collect`.
+pub struct Collector {
+    // a stack of the deferred values, it's only drained as a whole
+    head: AtomicPtr<Header>,
+}
+
+impl fmt::Debug for Collector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Collector")
+            .field("is_empty", &self.is_empty())
+            .finish()
+    }
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Collector::new()
+    }
+}
+
+impl Drop for Collector {
+    fn drop(&mut self) {
+        self.collect();
+    }
+}
+
+impl Collector {
+    /// create an empty collector
+    #[inline]
+    pub const fn new() -> Self {
+        Collector {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// check if there is no value waiting to be dropped
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Relaxed).is_null()
+    }
+
+    /// queue a value to be dropped by the next `collect`
+    pub fn defer<T: Send + 'static>(&self, value: T) {
+        let node = Box::into_raw(Box::new(Node {
+            header: Header {
+                next: ptr::null_mut(),
+                drop: drop_node::<T>,
+            },
+            value,
+        }))
+        .cast::<Header>();
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*node).next = head };
+            match self
+                .head
+                .compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(cur) => head = cur,
+            }
+        }
+    }
+
+    /// drop all the queued values and return the number of them
+    pub fn collect(&self) -> usize {
+        let mut node = self.head.swap(ptr::null_mut(), Ordering::Acquire);
+        let mut count = 0;
+        while !node.is_null() {
+            let next = unsafe { (*node).next };
+            unsafe { ((*node).drop)(node) };
+            node = next;
+            count += 1;
+        }
+        count
+    }
+}
+
+pub(crate) static GLOBAL: Collector = Collector::new();
+
+/// Drop all the values deferred by `RcuCell::write_deferred` and return the
+/// number of them.
+#[inline]
+pub fn collect() -> usize {
+    GLOBAL.collect()
+}
@@ -0,0 +1,70 @@
+use alloc::sync::Arc;
+
+use crate::RcuCell;
+
+/// A view of an `Option<U>` field of the value stored in an `RcuCell<T>`,
+/// created by `RcuCell::split_option`.
+///
+/// It behaves like an `RcuCell<U>` of its own, so the call sites that only
+/// care about the field don't need to know the parent type. Writes rebuild
+/// the parent value under its update lock.
+pub struct RcuOptionField<'a, T, U> {
+    cell: &'a RcuCell<T>,
+    get: fn(&T) -> &Option<U>,
+    set: fn(&mut T, Option<U>),
+}
+
+impl<T, U> core::fmt::Debug for RcuOptionField<'_, T, U> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("RcuOptionField").finish_non_exhaustive()
+    }
+}
+
+impl<'a, T: Clone, U: Clone> RcuOptionField<'a, T, U> {
+    pub(crate) fn new(
+        cell: &'a RcuCell<T>,
+        get: fn(&T) -> &Option<U>,
+        set: fn(&mut T, Option<U>),
+    ) -> Self {
+        RcuOptionField { cell, get, set }
+    }
+
+    /// check if the field is empty, an empty parent also has an empty field
+    #[inline]
+    pub fn is_none(&self) -> bool {
+        self.cell.read().is_none_or(|v| (self.get)(&v).is_none())
+    }
+
+    /// read out the field cloned from the current snapshot of the parent
+    #[inline]
+    pub fn read(&self) -> Option<Arc<U>> {
+        let parent = self.cell.read()?;
+        (self.get)(&parent).clone().map(Arc::new)
+    }
+
+    /// write the field by rebuilding the parent value,
+    /// return false if the parent cell is empty
+    pub fn set(&self, data: Option<U>) -> bool {
+        let mut data = Some(data);
+        self.cell.update(|old| {
+            old.map(|v| {
+                let mut v = T::clone(&v);
+                (self.set)(&mut v, data.take().unwrap());
+                v
+            })
+        });
+        data.is_none()
+    }
+
+    /// write a value to the field, return false if the parent cell is empty
+    #[inline]
+    pub fn write(&self, data: U) -> bool {
+        self.set(Some(data))
+    }
+
+    /// clear the field, return false if the parent cell is empty
+    #[inline]
+    pub fn take(&self) -> bool {
+        self.set(None)
+    }
+}
@@ -0,0 +1,104 @@
+use alloc::sync::Arc;
+
+use crate::sync::const_fn;
+use crate::RcuUnsized;
+
+type DynFn<Args, Out> = dyn Fn(Args) -> Out + Send + Sync;
+
+/// RCU cell of a callback, the callback can be hot swapped while other
+/// threads are calling it
+///
+/// The callback is stored as an `Arc<dyn Fn>` in an `RcuUnsized`, so an
+/// installed callback can be shared with other cells and registries.
+pub struct RcuFnCell<Args, Out> {
+    cell: RcuUnsized<DynFn<Args, Out>>,
+}
+
+impl<Args, Out> Default for RcuFnCell<Args, Out> {
+    fn default() -> Self {
+        RcuFnCell::none()
+    }
+}
+
+impl<Args, Out> core::fmt::Debug for RcuFnCell<Args, Out> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("RcuFnCell")
+            .field("is_none", &self.is_none())
+            .finish()
+    }
+}
+
+impl<Args, Out> RcuFnCell<Args, Out> {
+    const_fn! {
+        /// create an empty callback cell
+        #[inline]
+        pub const fn none() -> Self {
+            RcuFnCell {
+                cell: RcuUnsized::none(),
+            }
+        }
+    }
+
+    /// create callback cell from a closure
+    #[inline]
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(Args) -> Out + Send + Sync + 'static,
+    {
+        RcuFnCell {
+            cell: RcuUnsized::new(Arc::new(f) as Arc<DynFn<Args, Out>>),
+        }
+    }
+
+    /// create callback cell from a shared callback
+    #[inline]
+    pub fn from_arc(f: Arc<DynFn<Args, Out>>) -> Self {
+        RcuFnCell {
+            cell: RcuUnsized::new(f),
+        }
+    }
+
+    /// check if there is no callback installed
+    #[inline]
+    pub fn is_none(&self) -> bool {
+        self.cell.is_none()
+    }
+
+    /// install a new callback, the old one is dropped once all the
+    /// in-flight calls are finished
+    #[inline]
+    pub fn write<F>(&self, f: F)
+    where
+        F: Fn(Args) -> Out + Send + Sync + 'static,
+    {
+        self.cell.write(Arc::new(f) as Arc<DynFn<Args, Out>>);
+    }
+
+    /// install a shared callback and return the old one
+    #[inline]
+    pub fn set(&self, f: Option<Arc<DynFn<Args, Out>>>) -> Option<Arc<DynFn<Args, Out>>> {
+        self.cell.set(f)
+    }
+
+    /// read out the current callback
+    #[inline]
+    pub fn read(&self) -> Option<Arc<DynFn<Args, Out>>> {
+        self.cell.read()
+    }
+
+    /// remove the installed callback
+    #[inline]
+    pub fn take(&self) {
+        self.cell.take();
+    }
+
+    /// call the current callback, return `None` if there is no callback
+    ///
+    /// the callback is read only once, so a concurrent swap never makes the
+    /// call observe two different callbacks, and the callback runs on its
+    /// own `Arc` instead of the reader count of the cell
+    #[inline]
+    pub fn call(&self, args: Args) -> Option<Out> {
+        self.cell.read().map(|f| f(args))
+    }
+}
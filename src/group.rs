@@ -0,0 +1,176 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::backoff::Backoff;
+use crate::{ArcPointer, RcuCell};
+
+// end the commit of a writer even if it panics, or the snapshots would
+// wait for it forever
+struct Unlock<'a>(&'a AtomicUsize, usize);
+
+impl Drop for Unlock<'_> {
+    fn drop(&mut self) {
+        self.0.store(self.1 + 2, Ordering::Release);
+    }
+}
+
+/// A group of rcu cells under a shared sequence counter.
+///
+/// Readers can take a consistent snapshot of all the cells, and writers
+/// commit multi-cell updates that the snapshots observe atomically.
+/// Writers of the group are serialized, and a snapshot retries if a commit
+/// happens in the middle of it.
+#[derive(Debug)]
+pub struct RcuGroup<T> {
+    cells: Vec<RcuCell<T>>,
+    // odd while a writer is committing
+    seq: AtomicUsize,
+}
+
+/// Statistics of a `RcuGroup::clear_all` teardown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClearStats {
+    /// the number of cells that held a value
+    pub cleared: usize,
+    /// the number of values dropped by the teardown
+    pub dropped: usize,
+    /// the number of values still shared by others after quiescence
+    pub stragglers: usize,
+    /// the estimated bytes released by the dropped values
+    pub bytes: usize,
+}
+
+impl<T> From<Vec<RcuCell<T>>> for RcuGroup<T> {
+    fn from(cells: Vec<RcuCell<T>>) -> Self {
+        RcuGroup {
+            cells,
+            seq: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<T> FromIterator<RcuCell<T>> for RcuGroup<T> {
+    fn from_iter<I: IntoIterator<Item = RcuCell<T>>>(iter: I) -> Self {
+        Self::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl<T> RcuGroup<T> {
+    /// create a group of `len` empty cells
+    pub fn new(len: usize) -> Self {
+        (0..len).map(|_| RcuCell::none()).collect()
+    }
+
+    /// the number of cells in the group
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// check if the group has no cells
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// read out the value of a single cell, panics if `index` is out of range
+    #[inline]
+    pub fn read(&self, index: usize) -> Option<Arc<T>> {
+        self.cells[index].read()
+    }
+
+    /// take a consistent snapshot of all the cells
+    pub fn snapshot(&self) -> Vec<Option<Arc<T>>> {
+        let mut values = Vec::with_capacity(self.cells.len());
+        let backoff = Backoff::new();
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq & 1 == 0 {
+                values.extend(self.cells.iter().map(RcuCell::read));
+                core::sync::atomic::fence(Ordering::Acquire);
+                if self.seq.load(Ordering::Relaxed) == seq {
+                    return values;
+                }
+                values.clear();
+            }
+            backoff.snooze();
+        }
+    }
+
+    /// write a value to a single cell and return the old value,
+    /// panics if `index` is out of range
+    pub fn write(&self, index: usize, data: impl Into<Arc<T>>) -> Option<Arc<T>> {
+        let _unlock = self.lock();
+        self.cells[index].write(data)
+    }
+
+    /// Atomicly update several cells with a closure and return the old
+    /// snapshot. The closure is called with a snapshot of all the cells,
+    /// every slot it changes is published to the matching cell before any
+    /// reader can take a new snapshot.
+    pub fn update<F>(&self, f: F) -> Vec<Option<Arc<T>>>
+    where
+        F: FnOnce(&mut [Option<Arc<T>>]),
+    {
+        let _unlock = self.lock();
+        let old: Vec<_> = self.cells.iter().map(RcuCell::read).collect();
+        let mut new = old.clone();
+        f(&mut new);
+        for ((cell, old), new) in self.cells.iter().zip(&old).zip(new) {
+            if old.as_ptr() != new.as_ptr() {
+                cell.set(new);
+            }
+        }
+        old
+    }
+
+    /// Take the values of all the cells in one commit, then wait a while
+    /// for the in-flight readers to release them and drop them.
+    ///
+    /// The values still referenced by others after that are counted as
+    /// stragglers, they are dropped by whoever holds the last reference.
+    pub fn clear_all(&self) -> ClearStats {
+        let unlock = self.lock();
+        let old: Vec<_> = self.cells.iter().map(RcuCell::take).collect();
+        drop(unlock);
+
+        let mut stats = ClearStats::default();
+        for value in old.into_iter().flatten() {
+            stats.cleared += 1;
+            let backoff = Backoff::new();
+            while Arc::strong_count(&value) > 1 && !backoff.is_completed() {
+                backoff.snooze();
+            }
+            let bytes = core::mem::size_of_val(&*value);
+            match Arc::try_unwrap(value) {
+                Ok(v) => {
+                    drop(v);
+                    stats.dropped += 1;
+                    stats.bytes += bytes;
+                }
+                Err(_) => stats.stragglers += 1,
+            }
+        }
+        stats
+    }
+
+    // wait for the other writers and mark the group as being written,
+    // the commit ends when the returned guard is dropped
+    fn lock(&self) -> Unlock<'_> {
+        let backoff = Backoff::new();
+        loop {
+            let seq = self.seq.load(Ordering::Relaxed);
+            if seq & 1 == 0
+                && self
+                    .seq
+                    .compare_exchange_weak(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                core::sync::atomic::fence(Ordering::Release);
+                return Unlock(&self.seq, seq);
+            }
+            backoff.snooze();
+        }
+    }
+}
@@ -0,0 +1,384 @@
+//! An rcu cell whose readers are protected by hazard pointers.
+//!
+//! The value is a plain `Box` instead of an `Arc`, a reader publishes the
+//! pointer it's reading in a hazard record and gets a guard that derefs to
+//! the value. A writer retires the old value instead of dropping it, and the
+//! retired values are freed in batches once no hazard record protects them.
+//!
+//! A reader never blocks the writers nor the reclamation of the values it
+//! isn't reading, so the number of values waiting to be freed stays bounded
+//! even if a reader stalls forever while holding a guard.
+//!
+//! ```
+//! use rcu_cell::hazard::RcuCell;
+//!
+//! let cell = RcuCell::some(1);
+//! let guard = cell.read().unwrap();
+//! cell.write(2);
+//! // the old value is still valid while the guard is alive
+//! assert_eq!(*guard, 1);
+//! drop(guard);
+//! assert_eq!(*cell.read().unwrap(), 2);
+//! ```
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{fence, AtomicBool, AtomicPtr, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use crate::park;
+
+// scan the hazard records once this many values are retired
+const RETIRE_THRESHOLD: usize = 64;
+
+// a hazard record, the records are leaked and reused by the readers
+struct Record {
+    ptr: AtomicPtr<()>,
+    active: AtomicBool,
+    next: *const Record,
+}
+
+unsafe impl Sync for Record {}
+
+static RECORDS: AtomicPtr<Record> = AtomicPtr::new(ptr::null_mut());
+
+// a retired value with the function that drops it
+struct Retired {
+    ptr: *mut (),
+    drop: unsafe fn(*mut ()),
+}
+
+// only values of `Send` types are retired
+unsafe impl Send for Retired {}
+
+static RETIRED: Mutex<Vec<Retired>> = Mutex::new(Vec::new());
+
+unsafe fn drop_box<T>(ptr: *mut ()) {
+    drop(unsafe { Box::from_raw(ptr as *mut T) });
+}
+
+// get an unused hazard record, a new one is allocated if all of them
+// are in use
+fn acquire() -> &'static Record {
+    let mut p = RECORDS.load(Ordering::Acquire);
+    while let Some(record) = unsafe { p.as_ref() } {
+        if !record.active.load(Ordering::Relaxed) && !record.active.swap(true, Ordering::Acquire) {
+            return record;
+        }
+        p = record.next as *mut Record;
+    }
+
+    let record = Box::leak(Box::new(Record {
+        ptr: AtomicPtr::new(ptr::null_mut()),
+        active: AtomicBool::new(true),
+        next: ptr::null(),
+    }));
+    let mut head = RECORDS.load(Ordering::Relaxed);
+    loop {
+        record.next = head;
+        match RECORDS.compare_exchange_weak(head, record, Ordering::Release, Ordering::Relaxed) {
+            Ok(_) => return record,
+            Err(h) => head = h,
+        }
+    }
+}
+
+// hand the old value of a cell over to the reclamation
+fn retire<T: Send + 'static>(ptr: *mut T) {
+    if ptr.is_null() {
+        return;
+    }
+    let retired = Retired {
+        ptr: ptr as *mut (),
+        drop: drop_box::<T>,
+    };
+    let len = {
+        let mut list = RETIRED.lock().unwrap_or_else(PoisonError::into_inner);
+        list.push(retired);
+        list.len()
+    };
+    if len >= RETIRE_THRESHOLD {
+        reclaim();
+    }
+}
+
+/// Free the retired values that no reader is protecting.
+///
+/// The writers call it every time enough values are retired, call it to
+/// free the old values earlier, for example before checking a leak.
+pub fn reclaim() {
+    let mut list = core::mem::take(&mut *RETIRED.lock().unwrap_or_else(PoisonError::into_inner));
+    if list.is_empty() {
+        return;
+    }
+    // pairs with the fence of the readers, either the reader sees the
+    // value is unlinked or we see its hazard pointer
+    fence(Ordering::SeqCst);
+    let mut protected = Vec::new();
+    let mut p = RECORDS.load(Ordering::Acquire);
+    while let Some(record) = unsafe { p.as_ref() } {
+        let ptr = record.ptr.load(Ordering::Acquire);
+        if !ptr.is_null() {
+            protected.push(ptr);
+        }
+        p = record.next as *mut Record;
+    }
+    protected.sort_unstable();
+
+    list.retain(|r| {
+        if protected.binary_search(&r.ptr).is_ok() {
+            return true;
+        }
+        // the drop may retire other values, the list is not locked here
+        unsafe { (r.drop)(r.ptr) };
+        false
+    });
+    if !list.is_empty() {
+        RETIRED
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .append(&mut list);
+    }
+}
+
+/// The guard of a value read from `hazard::RcuCell`, the value is not
+/// freed until the guard is dropped
+pub struct Guard<'a, T: ?Sized> {
+    ptr: NonNull<T>,
+    record: &'static Record,
+    _cell: PhantomData<&'a T>,
+}
+
+impl<'a, T: ?Sized> Guard<'a, T> {
+    /// Make a guard of a part of the guarded value, like a field of it.
+    /// The whole value is still protected until the new guard is dropped,
+    /// so no `Arc` is cloned and no value is copied.
+    ///
+    /// It's an associated function, so it doesn't shadow a `map` method of
+    /// the value, call it as `Guard::map(guard, |v| &v.field)`.
+    #[inline]
+    pub fn map<U: ?Sized + 'a, F>(orig: Self, f: F) -> Guard<'a, U>
+    where
+        F: FnOnce(&T) -> &U,
+    {
+        let ptr = NonNull::from(f(&*orig));
+        let orig = core::mem::ManuallyDrop::new(orig);
+        Guard {
+            ptr,
+            record: orig.record,
+            _cell: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for Guard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Guard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized> Drop for Guard<'_, T> {
+    fn drop(&mut self) {
+        self.record.ptr.store(ptr::null_mut(), Ordering::Release);
+        self.record.active.store(false, Ordering::Release);
+    }
+}
+
+// release the writer lock even if the writer panics, or the other
+// writers would wait for it forever
+struct Unlock<'a, T>(&'a RcuCell<T>);
+
+impl<T> Drop for Unlock<'_, T> {
+    fn drop(&mut self) {
+        self.0.unlock();
+    }
+}
+
+/// RCU cell protected by hazard pointers, it behaves like
+/// `RwLock<Option<Box<T>>>`
+pub struct RcuCell<T> {
+    ptr: AtomicPtr<T>,
+    // serialize the writers, the readers never take it
+    locked: AtomicBool,
+    _marker: PhantomData<Box<T>>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for RcuCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RcuCell")
+            .field("value", &self.read())
+            .finish()
+    }
+}
+
+unsafe impl<T: Send> Send for RcuCell<T> {}
+unsafe impl<T: Send + Sync> Sync for RcuCell<T> {}
+
+impl<T> Drop for RcuCell<T> {
+    fn drop(&mut self) {
+        // no guard borrows the cell anymore, and the value is not retired
+        let ptr = *self.ptr.get_mut();
+        if !ptr.is_null() {
+            drop(unsafe { Box::from_raw(ptr) });
+        }
+    }
+}
+
+impl<T> Default for RcuCell<T> {
+    fn default() -> Self {
+        RcuCell::none()
+    }
+}
+
+impl<T> RcuCell<T> {
+    /// create an empty rcu cell
+    #[inline]
+    pub const fn none() -> Self {
+        RcuCell {
+            ptr: AtomicPtr::new(ptr::null_mut()),
+            locked: AtomicBool::new(false),
+            _marker: PhantomData,
+        }
+    }
+
+    /// create rcu cell from a value
+    #[inline]
+    pub fn some(data: T) -> Self {
+        RcuCell {
+            ptr: AtomicPtr::new(Box::into_raw(Box::new(data))),
+            locked: AtomicBool::new(false),
+            _marker: PhantomData,
+        }
+    }
+
+    /// create rcu cell from value that can be converted to Option<T>
+    #[inline]
+    pub fn new(data: impl Into<Option<T>>) -> Self {
+        match data.into() {
+            Some(data) => Self::some(data),
+            None => Self::none(),
+        }
+    }
+
+    /// convert the rcu cell to the inner value
+    #[inline]
+    pub fn into_inner(mut self) -> Option<T> {
+        let ptr = core::mem::replace(self.ptr.get_mut(), ptr::null_mut());
+        (!ptr.is_null()).then(|| *unsafe { Box::from_raw(ptr) })
+    }
+
+    /// get a mutable reference to the inner value through exclusive access
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.ptr.get_mut().as_mut() }
+    }
+
+    /// check if the rcu cell is empty
+    #[inline]
+    pub fn is_none(&self) -> bool {
+        self.ptr.load(Ordering::Acquire).is_null()
+    }
+
+    /// read the value, it's protected by a hazard pointer until the guard
+    /// is dropped
+    pub fn read(&self) -> Option<Guard<'_, T>> {
+        let mut ptr = self.ptr.load(Ordering::Acquire);
+        if ptr.is_null() {
+            return None;
+        }
+        let record = acquire();
+        loop {
+            record.ptr.store(ptr as *mut (), Ordering::Relaxed);
+            // pairs with the fence of `reclaim`
+            fence(Ordering::SeqCst);
+            let cur = self.ptr.load(Ordering::Acquire);
+            if cur == ptr {
+                break;
+            }
+            ptr = cur;
+            if ptr.is_null() {
+                record.ptr.store(ptr::null_mut(), Ordering::Release);
+                record.active.store(false, Ordering::Release);
+                return None;
+            }
+        }
+        Some(Guard {
+            ptr: unsafe { NonNull::new_unchecked(ptr) },
+            record,
+            _cell: PhantomData,
+        })
+    }
+
+    #[inline]
+    fn key(&self) -> usize {
+        &self.locked as *const _ as usize
+    }
+
+    // the lock is released when the returned guard is dropped
+    fn lock(&self) -> Unlock<'_, T> {
+        while self.locked.swap(true, Ordering::Acquire) {
+            park::wait_until(self.key(), || !self.locked.load(Ordering::Relaxed));
+        }
+        Unlock(self)
+    }
+
+    #[inline]
+    fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+        park::wake(self.key());
+    }
+}
+
+// the old values are dropped later by any thread
+impl<T: Send + 'static> RcuCell<T> {
+    /// write an option value to the rcu cell, the old value is freed once
+    /// no reader is reading it
+    pub fn set(&self, data: Option<T>) {
+        let new = data.map_or(ptr::null_mut(), |v| Box::into_raw(Box::new(v)));
+        let unlock = self.lock();
+        let old = self.ptr.swap(new, Ordering::AcqRel);
+        drop(unlock);
+        retire(old);
+    }
+
+    /// write a value to the rcu cell
+    #[inline]
+    pub fn write(&self, data: T) {
+        self.set(Some(data))
+    }
+
+    /// take the value out of the rcu cell, leave the rcu cell empty
+    #[inline]
+    pub fn take(&self) {
+        self.set(None)
+    }
+
+    /// Atomicly update the value with a closure, the closure is called with
+    /// the current value and the writers are serialized while it runs.
+    pub fn update<F>(&self, f: F)
+    where
+        F: FnOnce(Option<&T>) -> Option<T>,
+    {
+        let unlock = self.lock();
+        // the writers are locked out, so the value can't be retired
+        let old = self.ptr.load(Ordering::Acquire);
+        let new = f(unsafe { old.as_ref() });
+        let new = new.map_or(ptr::null_mut(), |v| Box::into_raw(Box::new(v)));
+        self.ptr.store(new, Ordering::Release);
+        drop(unlock);
+        retire(old);
+    }
+}
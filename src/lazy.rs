@@ -0,0 +1,139 @@
+use core::fmt;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::backoff::Backoff;
+use crate::sync::const_fn;
+use crate::RcuCell;
+
+const UNINIT: u8 = 0;
+const RUNNING: u8 = 1;
+const DONE: u8 = 2;
+
+// let the next thread run the init again if it panics
+struct Reset<'a>(&'a AtomicU8);
+
+impl Drop for Reset<'_> {
+    fn drop(&mut self) {
+        self.0.store(UNINIT, Ordering::Release);
+    }
+}
+
+/// RCU cell whose initial value is evaluated on the first access, declared
+/// with `rcu_static!`
+///
+/// `RcuCell::some` allocates, so a `static` rcu cell can't start with a
+/// value. This one starts empty and runs the init fn once, on the first
+/// deref, the other threads wait for it. It then derefs to the plain rcu
+/// cell, so the writes and the `take` work as usual and the init is never
+/// run again. A panicking init leaves the cell uninitialized, and an init
+/// that accesses its own cell never returns.
+pub struct LazyRcuCell<T> {
+    cell: RcuCell<T>,
+    state: AtomicU8,
+    init: fn() -> T,
+}
+
+impl<T: fmt::Debug> fmt::Debug for LazyRcuCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LazyRcuCell")
+            .field("cell", &self.cell)
+            .field("is_initialized", &self.is_initialized())
+            .finish()
+    }
+}
+
+impl<T> LazyRcuCell<T> {
+    const_fn! {
+        /// create the rcu cell with the init fn of its initial value
+        #[inline]
+        pub const fn new(init: fn() -> T) -> Self {
+            LazyRcuCell {
+                cell: RcuCell::none(),
+                state: AtomicU8::new(UNINIT),
+                init,
+            }
+        }
+    }
+
+    /// check if the initial value is evaluated
+    #[inline]
+    pub fn is_initialized(&self) -> bool {
+        self.state.load(Ordering::Acquire) == DONE
+    }
+
+    /// evaluate the initial value if it's not yet, and return the rcu cell
+    #[inline]
+    pub fn force(this: &Self) -> &RcuCell<T> {
+        if !this.is_initialized() {
+            this.init_slow();
+        }
+        &this.cell
+    }
+
+    #[cold]
+    fn init_slow(&self) {
+        let backoff = Backoff::new();
+        loop {
+            match self.state.compare_exchange_weak(
+                UNINIT,
+                RUNNING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let reset = Reset(&self.state);
+                    let value = (self.init)();
+                    core::mem::forget(reset);
+                    self.cell.write(value);
+                    self.state.store(DONE, Ordering::Release);
+                    return;
+                }
+                Err(DONE) => return,
+                Err(_) => backoff.snooze(),
+            }
+        }
+    }
+}
+
+impl<T> Deref for LazyRcuCell<T> {
+    type Target = RcuCell<T>;
+
+    #[inline]
+    fn deref(&self) -> &RcuCell<T> {
+        LazyRcuCell::force(self)
+    }
+}
+
+/// Declare the `static` rcu cells whose initial values are evaluated on the
+/// first access
+///
+/// The statics are `LazyRcuCell`s that deref to `RcuCell`.
+///
+/// ```
+/// use rcu_cell::rcu_static;
+///
+/// #[derive(Debug, Default)]
+/// struct Config {
+///     verbose: bool,
+/// }
+///
+/// rcu_static! {
+///     static CONFIG: Config = Config::default();
+///     pub(crate) static NAME: String = String::from("rcu");
+/// }
+///
+/// assert!(!CONFIG.read().unwrap().verbose);
+/// CONFIG.write(Config { verbose: true });
+/// assert!(CONFIG.read().unwrap().verbose);
+/// assert_eq!(NAME.read().unwrap().as_str(), "rcu");
+/// ```
+#[macro_export]
+macro_rules! rcu_static {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $init:expr;)*) => {
+        $(
+            $(#[$attr])*
+            $vis static $name: $crate::LazyRcuCell<$ty> = $crate::LazyRcuCell::new(|| $init);
+        )*
+    };
+}
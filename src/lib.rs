@@ -2,17 +2,94 @@
 #![no_std]
 
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
+mod array;
+mod backoff;
+#[cfg(feature = "bytes")]
+mod bytes_cell;
+mod cache;
+mod cell_of;
+#[cfg(feature = "cert-store")]
+mod cert_store;
+mod deferred;
+mod field;
+mod fn_cell;
+mod group;
+#[cfg(feature = "hazard")]
+pub mod hazard;
+mod lazy;
 mod link;
+pub mod map;
+mod non_empty;
+#[cfg(feature = "async")]
+mod notify;
+mod observed;
+mod ops;
+mod park;
+mod queue;
+mod rcu_box;
 mod rcu_cell;
 mod rcu_weak;
+#[cfg(feature = "std")]
+mod reaped;
+// the sequence numbers need 64-bit atomics
+#[cfg(any(target_has_atomic = "64", feature = "portable-atomic"))]
+mod ring;
+mod scoped;
+pub mod skip_map;
+mod slab;
+mod slice_cell;
+mod stack;
+mod sync;
+pub mod triple;
+mod unsized_cell;
+mod value;
+mod vec;
+pub mod watch;
+mod weak_list;
 
+pub use array::RcuArray;
+#[cfg(feature = "bytes")]
+pub use bytes_cell::RcuBytesCell;
+pub use cache::Cache;
+pub use cell_of::{RcuCellOf, RcuPointer};
+#[cfg(feature = "cert-store")]
+pub use cert_store::{CertSource, RcuCertStore, ReloadStats};
+pub use deferred::{collect, Collector};
+pub use field::RcuOptionField;
+pub use fn_cell::RcuFnCell;
+pub use group::{ClearStats, RcuGroup};
+pub use lazy::LazyRcuCell;
+#[cfg(feature = "stats")]
+pub use link::ContentionStats;
+#[cfg(feature = "panic-free")]
+pub use link::WouldBlock;
+pub use link::{CellState, Validation, ValueId};
+pub use non_empty::RcuCellNonEmpty;
+#[cfg(feature = "async")]
+pub use notify::{Changed, FilteredSubscription, Subscription, WatchMap};
+pub use observed::ObservedRcuCell;
+pub use ops::RcuCellOps;
+pub use queue::RcuQueue;
+pub use rcu_box::RcuBox;
 pub use rcu_cell::RcuCell;
+#[cfg(feature = "derive")]
+pub use rcu_cell_derive::RcuFields;
 pub use rcu_weak::RcuWeak;
-
-// we only support 64-bit platform
-const _: () = assert!(usize::MAX.count_ones() == 64);
-const _: () = assert!(core::mem::size_of::<*const ()>() == 8);
+#[cfg(feature = "std")]
+pub use reaped::{ReapedRcuCell, ReapedRef};
+#[cfg(any(target_has_atomic = "64", feature = "portable-atomic"))]
+pub use ring::RcuRing;
+pub use scoped::{ScopedRcuCell, ScopedRef};
+pub use slab::{RcuSlab, SlabKey};
+pub use slice_cell::{RcuBytes, RcuStr};
+pub use stack::RcuStack;
+pub use unsized_cell::RcuUnsized;
+pub use value::RcuValue;
+pub use vec::RcuVec;
+pub use weak_list::RcuWeakList;
 
 use alloc::sync::Arc;
 
@@ -119,91 +196,2471 @@ mod test {
     }
 
     #[test]
-    fn test_is_none() {
+    fn test_update_and_fetch() {
         let t = RcuCell::new(10);
-        assert!(!t.is_none());
-        t.take();
+        let (old, new) = t.update_and_fetch(|v| v.map(|x| *x + 1));
+        assert_eq!(old.map(|v| *v), Some(10));
+        assert!(t.arc_eq(new.as_ref().unwrap()));
+        let (old, new) = t.update_and_fetch(|_| None::<i32>);
+        assert_eq!(old.map(|v| *v), Some(11));
+        assert!(new.is_none());
         assert!(t.is_none());
     }
 
     #[test]
-    fn test_clone_rcu_cell() {
-        let t = Arc::new(RcuCell::new(Some(10)));
-        let t1 = t.clone();
-        let t2 = t.clone();
-        let t3 = t.clone();
-        t1.write(11);
-        drop(t1);
+    fn test_write_gen() {
+        let t = Arc::new(RcuCell::new(0));
+        let (old, gen) = t.write_gen(1);
+        assert_eq!(old.map(|v| *v), Some(0));
+        assert_eq!(gen, 1);
+        let (_, gen) = t.update_gen(|v| v.map(|x| *x + 1));
+        assert_eq!(gen, 2);
+        let (old, gen) = t.set_gen(None);
+        assert_eq!(old.map(|v| *v), Some(2));
+        assert_eq!(gen, 3);
+
+        // the generation order matches the publication order
+        extern crate std;
+        let handles: alloc::vec::Vec<_> = (0..4)
+            .map(|_| {
+                let t = t.clone();
+                std::thread::spawn(move || {
+                    let mut last = 0;
+                    for _ in 0..1000 {
+                        let (_, gen) = t.update_gen(|_| Some(0));
+                        assert!(gen > last);
+                        last = gen;
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(t.write_gen(1).1, 4004);
+    }
+
+    #[test]
+    fn test_update_mut() {
+        let t = RcuCell::new(alloc::vec![1, 2, 3]);
+        let ptr = Arc::as_ptr(&t.read().unwrap());
+        // unique, mutate in place
+        assert!(t.update_mut(|v| v.push(4)));
+        let v = t.read().unwrap();
+        assert_eq!(Arc::as_ptr(&v), ptr);
+        assert_eq!(*v, [1, 2, 3, 4]);
+        // shared with a reader, mutate a clone
+        assert!(t.update_mut(|v| v.push(5)));
+        assert_eq!(*v, [1, 2, 3, 4]);
+        assert_eq!(*t.read().unwrap(), [1, 2, 3, 4, 5]);
+        t.take();
+        assert!(!t.update_mut(|v| v.push(6)));
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut t = RcuCell::new(10);
+        *t.get_mut().unwrap() += 1;
         assert_eq!(t.read().map(|v| *v), Some(11));
-        t2.write(12);
-        drop(t2);
-        assert_eq!(t.read().map(|v| *v), Some(12));
-        t3.write(13);
-        drop(t3);
-        assert_eq!(t.read().map(|v| *v), Some(13));
+        let v = t.read().unwrap();
+        assert!(t.get_mut().is_none());
+        assert_eq!(t.into_inner(), Some(11));
+        assert_eq!(*v, 11);
+        let mut t = RcuCell::<i32>::none();
+        assert!(t.get_mut().is_none());
+        assert_eq!(t.into_inner(), None);
     }
 
     #[test]
-    fn test_rcu_reader() {
-        let t = Arc::new(RcuCell::new(10));
-        let t1 = t.clone();
-        let t2 = t.clone();
-        let t3 = t.clone();
-        let d1 = t1.read().unwrap();
-        let d3 = t3.read().unwrap();
-        t1.write(11);
-        let d2 = t2.read().unwrap();
-        assert_ne!(d1, d2);
-        assert_eq!(d1, d3);
-        assert_ne!(d2, d3);
+    fn test_read_into() {
+        let t = RcuCell::new(10);
+        let mut out = None;
+        t.read_into(&mut out);
+        assert!(t.arc_eq(out.as_ref().unwrap()));
+        t.write(11);
+        t.read_into(&mut out);
+        assert_eq!(out.as_deref(), Some(&11));
+        t.take();
+        t.read_into(&mut out);
+        assert!(out.is_none());
     }
 
     #[test]
-    fn test_rcu_take() {
-        let t = Arc::new(RcuCell::new(10));
-        let t1 = t.clone();
-        let t2 = t.clone();
-        let d1 = t1.take().unwrap();
-        assert_eq!(*d1, 10);
-        assert_eq!(t1.read(), None);
-        let d2 = t2.write(42);
-        assert!(d2.is_none());
-        let d3 = t2.read().unwrap();
-        assert_eq!(*d3, 42);
+    #[cfg(feature = "fair")]
+    fn fair_update() {
+        extern crate std;
+        use core::sync::atomic::AtomicBool;
+
+        let t = RcuCell::new(0usize);
+        let a_count = AtomicUsize::new(0);
+        let b_done = AtomicBool::new(false);
+        let rounds = 1000;
+        std::thread::scope(|s| {
+            // a greedy updater that never pauses
+            s.spawn(|| {
+                while !b_done.load(Ordering::Relaxed) {
+                    t.update(|v| {
+                        a_count.fetch_add(1, Ordering::Relaxed);
+                        v.map(|x| *x + 1)
+                    });
+                }
+            });
+            let mut waited = 0;
+            for _ in 0..rounds {
+                let before = a_count.load(Ordering::Relaxed);
+                t.update(|v| {
+                    waited += a_count.load(Ordering::Relaxed) - before;
+                    v.map(|x| *x + 1)
+                });
+            }
+            b_done.store(true, Ordering::Relaxed);
+            // each round only waits for the writers queued before it
+            assert!(waited <= rounds * 3, "writer starved: {waited}");
+        });
     }
 
     #[test]
-    fn test_arc_eq() {
+    fn test_downgrade() {
         let t = RcuCell::new(10);
-        let v = t.read().unwrap();
+        let w = t.downgrade();
+        assert_eq!(w.upgrade().map(|v| *v), Some(10));
+        let weak = super::RcuWeak::from(w);
+        assert!(weak.arc_eq(&t.read().unwrap()));
+        t.write(11);
+        assert!(weak.upgrade().is_none());
+        t.take();
+        assert!(t.downgrade().upgrade().is_none());
+    }
+
+    #[test]
+    fn test_weak_update() {
+        use super::RcuWeak;
+        use alloc::sync::Weak;
+
+        let a = Arc::new(10);
+        let w = RcuWeak::from(Weak::new());
+        // replace only if dangling
+        let old = w.update(|v| {
+            if v.upgrade().is_none() {
+                Arc::downgrade(&a)
+            } else {
+                v
+            }
+        });
+        assert!(old.upgrade().is_none());
+        assert!(w.arc_eq(&a));
+        let b = Arc::new(20);
+        w.update(|v| {
+            if v.upgrade().is_none() {
+                Arc::downgrade(&b)
+            } else {
+                v
+            }
+        });
+        assert!(w.arc_eq(&a));
+        drop(a);
+        w.update(|v| {
+            if v.upgrade().is_none() {
+                Arc::downgrade(&b)
+            } else {
+                v
+            }
+        });
+        assert_eq!(w.upgrade().map(|v| *v), Some(20));
+    }
+
+    #[test]
+    fn test_split_option() {
+        #[derive(Clone)]
+        struct Config {
+            name: &'static str,
+            proxy: Option<u16>,
+        }
+
+        let t = RcuCell::new(Config {
+            name: "a",
+            proxy: None,
+        });
+        let proxy = t.split_option(|c| &c.proxy, |c, v| c.proxy = v);
+        assert!(proxy.is_none());
+        assert!(proxy.write(8080));
+        assert_eq!(proxy.read().map(|v| *v), Some(8080));
+        assert_eq!(t.read().unwrap().name, "a");
+        assert!(proxy.take());
+        assert!(proxy.read().is_none());
+        t.take();
+        assert!(!proxy.write(8081));
+    }
+
+    #[test]
+    fn test_weak_cas() {
+        use super::RcuWeak;
+        use alloc::sync::Weak;
+
+        let a = Arc::new(10);
+        let b = Arc::new(20);
+        let w = RcuWeak::new();
+        // claim the empty slot
+        let old = w
+            .compare_exchange(&Weak::new(), Arc::downgrade(&a))
+            .unwrap();
+        assert!(old.upgrade().is_none());
+        let rejected = w.compare_exchange(&Weak::new(), Arc::downgrade(&b));
+        assert!(rejected.unwrap_err().ptr_eq(&Arc::downgrade(&b)));
+        let old = w.compare_exchange(&Arc::downgrade(&a), Arc::downgrade(&b));
+        assert!(old.unwrap().ptr_eq(&Arc::downgrade(&a)));
+        assert!(w.arc_eq(&b));
+    }
+
+    #[test]
+    fn test_non_empty() {
+        use super::RcuCellNonEmpty;
+
+        let t = RcuCellNonEmpty::new(1);
+        let v = t.read();
+        assert_eq!(*v, 1);
         assert!(t.arc_eq(&v));
+        assert_eq!(t.write(2), v);
+        assert_eq!(*t.update(|v| *v + 1), 2);
+        t.update_mut(|v| *v += 1);
+        assert_eq!(*t.read(), 4);
+        assert_eq!(t.version(), 3);
+        assert_eq!(t, RcuCellNonEmpty::new(4));
+        assert_eq!(t.into_inner(), 4);
+        let mut t = RcuCellNonEmpty::<i32>::default();
+        *t.get_mut().unwrap() += 1;
+        assert_eq!(*t.into_arc(), 1);
+    }
+
+    #[test]
+    fn test_eq() {
+        extern crate std;
+        use core::hash::{Hash, Hasher};
+        use std::collections::HashSet;
+
+        assert_eq!(RcuCell::new(1), RcuCell::new(1));
+        assert_ne!(RcuCell::new(1), RcuCell::new(2));
+        assert_ne!(RcuCell::new(1), RcuCell::none());
+        assert_eq!(RcuCell::<i32>::none(), RcuCell::none());
+        // the same Arc is equal without comparing the values
+        let nan = Arc::new(f64::NAN);
+        assert_ne!(RcuCell::new(f64::NAN), RcuCell::new(f64::NAN));
+        assert_eq!(RcuCell::from(nan.clone()), RcuCell::from(nan));
+
+        // key the cells by the identity of their values
+        struct ByPtr(RcuCell<i32>);
+        impl PartialEq for ByPtr {
+            fn eq(&self, other: &Self) -> bool {
+                RcuCell::ptr_eq(&self.0, &other.0)
+            }
+        }
+        impl Eq for ByPtr {}
+        impl Hash for ByPtr {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.ptr_hash(state);
+            }
+        }
+        let v = Arc::new(1);
+        // the cells are never written while they are keys
+        #[allow(clippy::mutable_key_type)]
+        let mut set = HashSet::new();
+        assert!(set.insert(ByPtr(RcuCell::from(v.clone()))));
+        assert!(!set.insert(ByPtr(RcuCell::from(v))));
+        assert!(set.insert(ByPtr(RcuCell::new(1))));
+    }
+
+    #[test]
+    fn test_debug_state() {
+        use alloc::format;
+
+        let t = RcuCell::new(10);
         t.write(11);
-        assert!(!t.arc_eq(&v));
-        let t1 = RcuCell::from(v.clone());
-        assert!(t1.arc_eq(&v));
-        let v2 = t.write(v);
-        let t2 = RcuCell::from(v2.clone());
-        assert!(RcuCell::ptr_eq(&t, &t1));
-        assert!(t2.arc_eq(v2.as_ref().unwrap()));
+        let state = t.debug_state();
+        assert_eq!(state.addr, Arc::as_ptr(&t.read().unwrap()).addr());
+        assert_eq!(state.readers, 0);
+        assert!(!state.updating);
+        assert_eq!(state.generation, 1);
+        let s = format!("{state}");
+        assert!(s.ends_with("readers=0 updating=false mutating=false generation=1"));
+        assert_eq!(
+            format!("{t:?}"),
+            "RcuCell { value: Some(11), generation: 1 }"
+        );
+        // never wait for the value that is being mutated in place
+        t.update_mut(|_| assert!(format!("{t:?}").contains("<locked>")));
+        let a = Arc::new(1);
+        let w = super::RcuWeak::from(Arc::downgrade(&a));
+        assert_eq!(format!("{w:?}"), "RcuWeak { value: Some(1) }");
+        drop(a);
+        assert_eq!(format!("{w:?}"), "RcuWeak { value: None }");
     }
 
     #[test]
-    fn cas_test() {
-        use super::ArcPointer;
-        use Ordering::SeqCst;
+    fn test_upgrade_or_init() {
+        extern crate std;
+        use super::RcuWeak;
 
-        let a = RcuCell::new(1234);
+        let w = RcuWeak::new();
+        let built = AtomicUsize::new(0);
+        let init = || {
+            built.fetch_add(1, Ordering::Relaxed);
+            Arc::new(42)
+        };
+        let values: alloc::vec::Vec<_> = std::thread::scope(|s| {
+            let handles: alloc::vec::Vec<_> = (0..4)
+                .map(|_| s.spawn(|| w.upgrade_or_init(init)))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(built.load(Ordering::Relaxed), 1);
+        assert!(values.iter().all(|v| Arc::ptr_eq(v, &values[0])));
+        drop(values);
+        assert_eq!(*w.upgrade_or_init(init), 42);
+        assert_eq!(built.load(Ordering::Relaxed), 2);
+    }
 
-        let curr = a.read().as_ptr();
-        let res1 = unsafe { a.compare_exchange(curr, None, SeqCst, SeqCst) }.unwrap();
-        assert_eq!(res1, curr);
+    #[test]
+    #[cfg(any(target_has_atomic = "64", feature = "portable-atomic"))]
+    fn test_ring() {
+        let ring = super::RcuRing::<u32, 4>::new();
+        assert!(ring.latest().is_none());
+        for i in 0..10 {
+            assert_eq!(ring.push(i), i as u64);
+        }
+        assert_eq!(ring.end(), 10);
+        assert_eq!(ring.latest().map(|(s, v)| (s, *v)), Some((9, 9)));
+        assert_eq!(ring.get(6).map(|v| *v), Some(6));
+        assert!(ring.get(5).is_none());
+        assert!(ring.get(10).is_none());
+    }
+
+    #[test]
+    fn test_write_arc_if_dead() {
+        let a = Arc::new(10);
+        let b = Arc::new(20);
+        let w = super::RcuWeak::new();
+        assert!(w.write_arc_if_dead(&a).is_ok());
+        let alive = w.write_arc_if_dead(&b).unwrap_err();
+        assert!(Arc::ptr_eq(&alive, &a));
+        drop((a, alive));
+        assert!(Arc::ptr_eq(&w.write_arc_if_dead(&b).unwrap(), &b));
+        assert!(w.arc_eq(&b));
+    }
+
+    #[test]
+    fn test_swap_cells() {
+        let a = RcuCell::new(1);
+        let b = RcuCell::none();
+        RcuCell::swap_cells(&a, &b);
         assert!(a.is_none());
-        let res2 = unsafe { a.compare_exchange(res1, Some(&Arc::new(5678)), SeqCst, SeqCst) };
-        assert!(res2.is_err());
+        assert_eq!(b.read().map(|v| *v), Some(1));
+        a.write(2);
+        RcuCell::swap_cells(&b, &a);
+        assert_eq!(a.read().map(|v| *v), Some(1));
+        assert_eq!(b.read().map(|v| *v), Some(2));
+        RcuCell::swap_cells(&a, &a);
+        assert_eq!(a.read().map(|v| *v), Some(1));
+    }
 
-        let null = core::ptr::null();
-        let res2 = unsafe { a.compare_exchange(null, Some(&Arc::new(5678)), SeqCst, SeqCst) };
-        assert!(res2.is_ok());
-        assert_eq!(a.read().map(|v| *v), Some(5678));
+    #[test]
+    fn test_write_bounded() {
+        extern crate std;
+        use core::sync::atomic::AtomicBool;
+
+        static LIVE: AtomicUsize = AtomicUsize::new(0);
+        struct Foo(usize);
+        impl Foo {
+            fn new(v: usize) -> Self {
+                LIVE.fetch_add(1, Ordering::Relaxed);
+                Foo(v)
+            }
+        }
+        impl Drop for Foo {
+            fn drop(&mut self) {
+                LIVE.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+
+        let t = RcuCell::new(Foo::new(0));
+        let stop = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    while !stop.load(Ordering::Relaxed) {
+                        let v = t.read().unwrap();
+                        assert!(v.0 < 1000);
+                    }
+                });
+            }
+            for i in 1..1000 {
+                let mut v = Arc::new(Foo::new(i));
+                // only the two-word layout gives the value back on a
+                // stalled reader, retry it
+                let old = loop {
+                    match t.write_bounded(v) {
+                        Ok(old) => break old.unwrap(),
+                        Err(back) => v = back,
+                    }
+                };
+                assert_eq!(old.0, i - 1);
+            }
+            stop.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(LIVE.load(Ordering::Relaxed), 1);
+        drop(t);
+        assert_eq!(LIVE.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    #[cfg(any(not(target_pointer_width = "64"), feature = "two-word"))]
+    fn update_bounded_stalled_reader() {
+        use super::link::{LinkWrapper, NoCredit};
+
+        let a = Arc::into_raw(Arc::new(1));
+        let b = Arc::into_raw(Arc::new(2));
+        let link = LinkWrapper::new(a);
+        // a reader stalls in the middle of a read
+        let ptr = link.inc_ref();
+        assert!(link.update_bounded(b, NoCredit).is_none());
+        // the readers are unblocked and the generation is untouched
+        assert_eq!(link.try_inc_ref(), Some(a));
+        assert!(!link.dec_ref(ptr));
+        assert!(!link.dec_ref(ptr));
+        assert_eq!(link.gen(), 0);
+        assert_eq!(link.update_bounded(b, NoCredit), Some((a, 1)));
+        drop(unsafe { Arc::from_raw(a) });
+        drop(unsafe { Arc::from_raw(link.get_ref()) });
+    }
+
+    #[test]
+    #[cfg(all(target_pointer_width = "64", not(feature = "two-word")))]
+    fn update_credit_laggards() {
+        use super::link::{Credit, LinkWrapper};
+
+        struct Once;
+        impl Credit<i32> for Once {
+            unsafe fn credit(&self, ptr: *const i32, n: isize) {
+                assert_eq!(n, 1);
+                Arc::increment_strong_count(ptr);
+            }
+        }
+
+        let a = Arc::new(1);
+        let b = Arc::new(2);
+        let link = LinkWrapper::new(Arc::into_raw(a.clone()));
+        // a reader stalls in the middle of a read
+        let ptr = link.inc_ref();
+        let (old, _gen) = link.update(Arc::into_raw(b.clone()), Once);
+        assert_eq!(old, ptr);
+        drop(unsafe { Arc::from_raw(old) });
+        // the laggard reader clones the Arc and finds its count credited
+        let v = core::mem::ManuallyDrop::new(unsafe { Arc::from_raw(ptr) });
+        let v = Arc::clone(&v);
+        assert_eq!(Arc::strong_count(&v), 3);
+        assert!(link.dec_ref(ptr));
+        unsafe { Arc::decrement_strong_count(ptr) };
+        drop(v);
+        assert_eq!(Arc::strong_count(&a), 1);
+        // readers of the current pointer release the count as usual
+        let ptr = link.inc_ref();
+        assert!(!link.dec_ref(ptr));
+        drop(unsafe { Arc::from_raw(link.get_ref()) });
+        assert_eq!(Arc::strong_count(&b), 1);
+    }
+
+    #[test]
+    #[cfg(all(target_pointer_width = "64", not(feature = "two-word")))]
+    fn reader_saturation() {
+        extern crate std;
+        use super::link::LinkWrapper;
+
+        struct Shared(LinkWrapper<i32>);
+        unsafe impl Sync for Shared {}
+
+        let a = Arc::new(1);
+        let shared = Shared(LinkWrapper::new(Arc::into_raw(a.clone())));
+        let shared = &shared;
+        let link = &shared.0;
+        let mut readers = 0;
+        while link.try_inc_ref().is_some() {
+            readers += 1;
+        }
+        assert!(link.validate().readers_overflow);
+        std::thread::scope(|s| {
+            let h = s.spawn(move || {
+                // spin until a reader releases instead of panicking
+                let link = &shared.0;
+                let ptr = link.inc_ref();
+                assert!(!link.dec_ref(ptr));
+            });
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            assert!(!h.is_finished());
+            assert!(!link.dec_ref(Arc::as_ptr(&a)));
+            readers -= 1;
+        });
+        for _ in 0..readers {
+            assert!(!link.dec_ref(Arc::as_ptr(&a)));
+        }
+        assert_eq!(link.validate().readers, 0);
+        drop(unsafe { Arc::from_raw(link.get_ref()) });
+        assert_eq!(Arc::strong_count(&a), 1);
+    }
+
+    #[test]
+    fn test_update_weak() {
+        extern crate std;
+        use core::sync::atomic::AtomicBool;
+
+        let t = RcuCell::new(0);
+        let mirror = super::RcuWeak::new();
+        let stop = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    while !stop.load(Ordering::Relaxed) {
+                        let v = *t.read().unwrap();
+                        // the mirror is never older than the cell
+                        if let Some(w) = mirror.upgrade() {
+                            assert!(*w >= v);
+                        }
+                    }
+                });
+            }
+            for i in 0..1000 {
+                let (old, old_weak) = t.update_weak(&mirror, |v| v.map(|v| *v + 1));
+                let expected = (i > 0).then_some(i);
+                assert_eq!(old_weak.upgrade().map(|v| *v), expected);
+                assert_eq!(old.map(|v| *v), Some(i));
+            }
+            stop.store(true, Ordering::Relaxed);
+        });
+        assert!(mirror.arc_eq(&t.read().unwrap()));
+        let (old, old_weak) = t.update_weak(&mirror, |_| None::<i32>);
+        assert!(old_weak.upgrade().is_some());
+        drop(old);
+        assert!(old_weak.upgrade().is_none());
+        assert!(t.is_none());
+        assert!(mirror.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_parked_writers() {
+        extern crate std;
+
+        let t = RcuCell::new(0);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                // hold the update lock long enough to park the other writers
+                t.update(|v| {
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                    v.map(|v| *v + 1)
+                });
+            });
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        t.update(|v| v.map(|v| *v + 1));
+                    }
+                });
+            }
+        });
+        assert_eq!(t.read().map(|v| *v), Some(401));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_wait_until() {
+        extern crate std;
+
+        let t = RcuCell::none();
+        assert_eq!(t.wait_until(|v| v.is_none()), None);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..10 {
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                    t.write(i);
+                }
+            });
+            let v = t.wait_until(|v| v.is_some_and(|v| *v >= 5));
+            assert!(*v.unwrap() >= 5);
+            assert_eq!(t.wait_until(|v| v == Some(&9)).map(|v| *v), Some(9));
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "may")]
+    fn test_coroutine_writers() {
+        extern crate std;
+        use alloc::sync::Arc;
+        use alloc::vec::Vec;
+        use may::{coroutine, go};
+
+        // all the coroutines share one thread, a writer that blocked the
+        // thread would never let the lock holder run again
+        may::config().set_workers(1);
+        let t = Arc::new(RcuCell::new(0));
+        let t1 = t.clone();
+        let holder = go!(move || {
+            t1.update(|v| {
+                for _ in 0..1000 {
+                    coroutine::yield_now();
+                }
+                v.map(|v| *v + 1)
+            });
+        });
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let t = t.clone();
+                go!(move || {
+                    for _ in 0..100 {
+                        t.update(|v| v.map(|v| *v + 1));
+                    }
+                })
+            })
+            .collect();
+        holder.join().unwrap();
+        for w in writers {
+            w.join().unwrap();
+        }
+        assert_eq!(t.read().map(|v| *v), Some(401));
+    }
+
+    // a waker that unparks the polling thread
+    #[cfg(feature = "async")]
+    fn thread_waker() -> core::task::Waker {
+        extern crate std;
+        use std::thread::{self, Thread};
+
+        struct Unparker(Thread);
+        impl std::task::Wake for Unparker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+        Arc::new(Unparker(thread::current())).into()
+    }
+
+    #[cfg(feature = "async")]
+    fn block_on<F: core::future::Future>(f: F) -> F::Output {
+        extern crate std;
+        use core::task::{Context, Poll};
+
+        let waker = thread_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut f = core::pin::pin!(f);
+        loop {
+            match f.as_mut().poll(&mut cx) {
+                Poll::Ready(v) => return v,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn test_changed() {
+        extern crate std;
+        use core::future::Future;
+        use core::task::Context;
+        use std::thread;
+
+        let t = RcuCell::new(0);
+        // a publication before polling still resolves it
+        let changed = t.changed();
+        t.write(1);
+        block_on(changed);
+        // a registered future can be dropped before it resolves
+        let mut changed = alloc::boxed::Box::pin(t.changed());
+        let waker = thread_waker();
+        assert!(changed
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker))
+            .is_pending());
+        drop(changed);
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                for i in 2..=10 {
+                    thread::sleep(std::time::Duration::from_millis(1));
+                    t.write(i);
+                }
+            });
+            block_on(t.changed());
+            let v = block_on(t.wait_for(|v| v.is_some_and(|v| *v >= 5)));
+            assert!(*v.unwrap() >= 5);
+            assert_eq!(block_on(t.wait_for(|v| v == Some(&10))), Some(Arc::new(10)));
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn test_subscribe() {
+        extern crate std;
+        use core::pin::Pin;
+        use futures_core::Stream;
+
+        let t = RcuCell::new(0);
+        let mut values = t.subscribe();
+        let mut next = || {
+            block_on(core::future::poll_fn(|cx| {
+                Pin::new(&mut values).poll_next(cx)
+            }))
+            .unwrap()
+            .map(|v| *v)
+        };
+        assert_eq!(next(), Some(0));
+        // the intermediate values are coalesced
+        t.write(1);
+        t.take();
+        t.write(2);
+        assert_eq!(next(), Some(2));
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 3..=10 {
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                    t.write(i);
+                }
+            });
+            let mut last = 2;
+            while last < 10 {
+                let v = next().unwrap();
+                assert!(v > last);
+                last = v;
+            }
+        });
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn test_subscribe_filtered() {
+        extern crate std;
+        use core::pin::Pin;
+        use futures_core::Stream;
+
+        let t = RcuCell::new(0);
+        // only the flips of the low bit
+        let mut flips = t.subscribe_filtered(|old, new| old.map(|v| v & 1) != new.map(|v| v & 1));
+        let mut next = || {
+            block_on(core::future::poll_fn(|cx| {
+                Pin::new(&mut flips).poll_next(cx)
+            }))
+            .unwrap()
+            .map(|v| *v)
+        };
+        // the current value is not yielded, the same parity is filtered
+        t.write(2);
+        t.write(4);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+                t.write(5);
+            });
+            assert_eq!(next(), Some(5));
+        });
+        t.write(7);
+        t.take();
+        assert_eq!(next(), None);
+        t.write(9);
+        assert_eq!(next(), Some(9));
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn test_watch_map() {
+        extern crate std;
+        use core::pin::Pin;
+        use futures_core::Stream;
+
+        let t = RcuCell::new((0, 0));
+        // only the first field is watched
+        let mut firsts = t.watch_map(|v| v.map(|v| v.0));
+        let mut next = || {
+            block_on(core::future::poll_fn(|cx| {
+                Pin::new(&mut firsts).poll_next(cx)
+            }))
+            .unwrap()
+        };
+        assert_eq!(next(), Some(0));
+        // the changes of the other field are skipped
+        t.write((0, 1));
+        t.write((0, 2));
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+                t.write((1, 2));
+            });
+            assert_eq!(next(), Some(1));
+        });
+        t.write((1, 3));
+        t.take();
+        assert_eq!(next(), None);
+        t.write((2, 0));
+        assert_eq!(next(), Some(2));
+    }
+
+    #[test]
+    fn test_observer() {
+        let writes = Arc::new(AtomicUsize::new(0));
+        let w = writes.clone();
+        let last = Arc::new(AtomicUsize::new(0));
+        let l = last.clone();
+        let t = RcuCell::new(1usize)
+            .with_observer(move |_, _| {
+                w.fetch_add(1, Ordering::Relaxed);
+            })
+            .with_observer(move |old, new| {
+                // the old value is the new value of the previous write
+                assert_eq!(old.copied().unwrap_or(0), l.load(Ordering::Relaxed));
+                l.store(new.copied().unwrap_or(0), Ordering::Relaxed);
+            });
+        last.store(1, Ordering::Relaxed);
+        assert_eq!(t.write(2).map(|v| *v), Some(1));
+        t.update(|v| v.map(|v| *v + 1));
+        assert_eq!(t.take().map(|v| *v), Some(3));
+        t.set(Some(Arc::new(5)));
+        assert_eq!(writes.load(Ordering::Relaxed), 4);
+        assert_eq!(last.load(Ordering::Relaxed), 5);
+        // the inner cell writes without the observers
+        let t = t.into_inner();
+        t.write(6);
+        assert_eq!(writes.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn test_watch() {
+        extern crate std;
+        use super::watch::{self, RecvError, SendError};
+
+        let (tx, mut rx) = watch::channel(0);
+        let mut rx2 = rx.clone();
+        assert_eq!(tx.receiver_count(), 2);
+        assert_eq!(rx.has_changed(), Ok(false));
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for i in 1..=100 {
+                    tx.send(i).unwrap();
+                }
+            });
+            // a receiver may skip values but never sees them out of order
+            let mut last = 0;
+            while rx.wait_changed().is_ok() {
+                let v = *rx.borrow_and_update();
+                assert!(v > last);
+                last = v;
+            }
+            assert_eq!(last, 100);
+        });
+        assert_eq!(rx.has_changed(), Err(RecvError));
+        // the unseen value is still reported after the sender is dropped
+        assert_eq!(rx2.has_changed(), Ok(true));
+        assert_eq!(rx2.wait_changed(), Ok(()));
+        assert_eq!(*rx2.borrow(), 100);
+        assert_eq!(rx2.wait_changed(), Err(RecvError));
+
+        let (tx, rx) = watch::channel(0);
+        let rx2 = tx.subscribe();
+        drop(rx);
+        assert_eq!(tx.send(1), Ok(()));
+        drop(rx2);
+        assert_eq!(tx.send(2), Err(SendError(2)));
+        assert_eq!(*tx.send_replace(3), 1);
+        assert_eq!(*tx.borrow(), 3);
+
+        #[cfg(feature = "async")]
+        {
+            let (tx, mut rx) = watch::channel(0);
+            std::thread::scope(|s| {
+                s.spawn(move || {
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                    tx.send(1).unwrap();
+                });
+                assert_eq!(block_on(rx.changed()), Ok(()));
+                assert_eq!(*rx.borrow(), 1);
+                assert_eq!(block_on(rx.changed()), Err(RecvError));
+            });
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "cert-store")]
+    fn test_cert_store() {
+        use super::{CertSource, RcuCertStore, ReloadStats};
+        use core::cell::Cell;
+
+        // fails on every odd load
+        struct Source(Cell<u32>);
+        impl CertSource for Source {
+            type Cert = u32;
+            type Error = u32;
+            fn load(&self) -> Result<u32, u32> {
+                let n = self.0.get();
+                self.0.set(n + 1);
+                if n.is_multiple_of(2) {
+                    Ok(n)
+                } else {
+                    Err(n)
+                }
+            }
+        }
+
+        let source = Source(Cell::new(0));
+        let store = RcuCertStore::load(&source).unwrap();
+        let cert = store.current();
+        assert_eq!(*cert, 0);
+        assert_eq!(store.reload(&source), Err(1));
+        assert_eq!(*store.current(), 0);
+        assert_eq!(store.reload(&source).unwrap(), cert);
+        assert_eq!(*store.current(), 2);
+        assert_eq!(*store.store(42), 2);
+        assert_eq!(store.version(), 2);
+        assert_eq!(
+            store.stats(),
+            ReloadStats {
+                reloads: 2,
+                failures: 1
+            }
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "bytes")]
+    fn test_bytes_cell() {
+        use super::RcuBytesCell;
+        use bytes::Bytes;
+
+        let t = RcuBytesCell::default();
+        assert!(t.read().is_empty());
+        let cert = Bytes::from_static(b"cert v1");
+        assert!(t.write(cert.clone()).is_empty());
+        let v1 = t.read();
+        assert_eq!(v1, cert);
+        assert_eq!(v1.as_ptr(), cert.as_ptr());
+        // equal content but a different view is not the same
+        let copy = Bytes::copy_from_slice(b"cert v1");
+        assert_eq!(
+            t.compare_exchange(&copy, "cert v2".into()).unwrap_err(),
+            "cert v2"
+        );
+        assert_eq!(
+            t.compare_exchange(&v1.slice(1..), "x".into()).unwrap_err(),
+            "x"
+        );
+        assert_eq!(t.compare_exchange(&v1, "cert v2".into()).unwrap(), cert);
+        assert_eq!(t.read(), "cert v2");
+        assert_eq!(t.write(Bytes::new()), "cert v2");
+    }
+
+    #[test]
+    fn test_group() {
+        extern crate std;
+        use super::RcuGroup;
+        use core::sync::atomic::AtomicBool;
+
+        let group = RcuGroup::from(alloc::vec![RcuCell::new(0), RcuCell::new(0)]);
+        assert_eq!(group.len(), 2);
+        let stop = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    let snap = group.snapshot();
+                    assert_eq!(snap[0], snap[1]);
+                }
+            });
+            for i in 1..1000 {
+                group.update(|values| {
+                    values[0] = Some(Arc::new(i));
+                    values[1] = Some(Arc::new(i));
+                });
+            }
+            stop.store(true, Ordering::Relaxed);
+        });
+        let old = group.write(1, 42);
+        assert_eq!(old.map(|v| *v), Some(999));
+        assert_eq!(group.read(1).map(|v| *v), Some(42));
+        // a panicking writer still ends its commit
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        let r = catch_unwind(AssertUnwindSafe(|| group.update(|_| panic!("boom"))));
+        assert!(r.is_err());
+        assert!(catch_unwind(AssertUnwindSafe(|| group.write(2, 0))).is_err());
+        assert_eq!(group.snapshot().len(), 2);
+        assert!(RcuGroup::<u8>::new(3)
+            .snapshot()
+            .iter()
+            .all(Option::is_none));
+    }
+
+    #[test]
+    fn test_group_clear_all() {
+        use super::{ClearStats, RcuGroup};
+
+        let group: RcuGroup<u64> = (0..4).map(RcuCell::new).collect();
+        group.write(3, 30);
+        group.update(|v| v[2] = None);
+        let held = group.read(1).unwrap();
+        let stats = group.clear_all();
+        assert_eq!(
+            stats,
+            ClearStats {
+                cleared: 3,
+                dropped: 2,
+                stragglers: 1,
+                bytes: 16,
+            }
+        );
+        assert_eq!(*held, 1);
+        assert!(group.snapshot().iter().all(Option::is_none));
+        assert_eq!(group.clear_all(), ClearStats::default());
+    }
+
+    #[test]
+    fn test_identity() {
+        let t = RcuCell::new(1);
+        let id = t.identity();
+        assert_eq!(t.identity(), id);
+        let v = t.read().unwrap();
+        t.write(v);
+        let id2 = t.identity();
+        assert_ne!(id2, id);
+        assert_eq!(id2.generation(), id.generation() + 1);
+        let addr = alloc::format!("{:x}", t.debug_state().addr);
+        assert!(!alloc::format!("{id2}").contains(&addr));
+    }
+
+    #[test]
+    #[cfg(feature = "panic-free")]
+    fn test_try_ops() {
+        use super::WouldBlock;
+
+        let t = RcuCell::new(1);
+        assert_eq!(t.try_write(2).unwrap().map(|v| *v), Some(1));
+        t.update(|v| {
+            assert_eq!(t.try_write(3).map(|_| ()).map_err(|v| *v), Err(3));
+            assert!(t.try_set(None).is_err());
+            v.map(|v| *v + 1)
+        });
+        t.update_mut(|v| {
+            assert_eq!(t.try_read(), Err(WouldBlock));
+            *v += 1;
+        });
+        assert_eq!(t.try_read().unwrap().map(|v| *v), Some(4));
+        assert_eq!(t.try_set(None).unwrap().map(|v| *v), Some(4));
+        assert_eq!(t.try_read(), Ok(None));
+    }
+
+    #[test]
+    fn test_version() {
+        let t = RcuCell::new(1);
+        let v0 = t.version();
+        assert_eq!(t.version(), v0);
+        t.write(2);
+        let v1 = t.version();
+        assert!(v1 > v0);
+        t.update(|v| v.map(|x| *x + 1));
+        t.take();
+        assert_eq!(t.version(), v1 + 2);
+    }
+
+    #[test]
+    fn test_read_unprotected() {
+        let t = RcuCell::new(10);
+        let v = t.read().unwrap();
+        assert_eq!(unsafe { t.read_unprotected() }, Arc::as_ptr(&v));
+        assert_eq!(unsafe { t.deref_unprotected() }, Some(&10));
+        // the old value is retained by `v`, like a grace period would do
+        t.write(11);
+        assert_eq!(unsafe { t.deref_unprotected() }, Some(&11));
+        t.take();
+        assert!(unsafe { t.read_unprotected() }.is_null());
+        assert_eq!(*v, 10);
+    }
+
+    #[test]
+    fn test_cache() {
+        let t = Arc::new(RcuCell::new(10));
+        let mut cache = super::Cache::new(t.clone());
+        let first = cache.load().clone().unwrap();
+        assert!(Arc::ptr_eq(cache.load().as_ref().unwrap(), &first));
+        t.write(11);
+        assert_eq!(cache.load().as_deref(), Some(&11));
+        t.take();
+        assert!(cache.load().is_none());
+        let mut cache = super::Cache::new(&*t);
+        assert!(cache.load().is_none());
+        t.write(12);
+        assert_eq!(cache.load().as_deref(), Some(&12));
+    }
+
+    #[test]
+    fn test_is_none() {
+        let t = RcuCell::new(10);
+        assert!(!t.is_none());
+        t.take();
+        assert!(t.is_none());
+    }
+
+    #[test]
+    fn test_clone_rcu_cell() {
+        let t = Arc::new(RcuCell::new(Some(10)));
+        let t1 = t.clone();
+        let t2 = t.clone();
+        let t3 = t.clone();
+        t1.write(11);
+        drop(t1);
+        assert_eq!(t.read().map(|v| *v), Some(11));
+        t2.write(12);
+        drop(t2);
+        assert_eq!(t.read().map(|v| *v), Some(12));
+        t3.write(13);
+        drop(t3);
+        assert_eq!(t.read().map(|v| *v), Some(13));
+    }
+
+    #[test]
+    fn test_rcu_reader() {
+        let t = Arc::new(RcuCell::new(10));
+        let t1 = t.clone();
+        let t2 = t.clone();
+        let t3 = t.clone();
+        let d1 = t1.read().unwrap();
+        let d3 = t3.read().unwrap();
+        t1.write(11);
+        let d2 = t2.read().unwrap();
+        assert_ne!(d1, d2);
+        assert_eq!(d1, d3);
+        assert_ne!(d2, d3);
+    }
+
+    #[test]
+    fn test_rcu_take() {
+        let t = Arc::new(RcuCell::new(10));
+        let t1 = t.clone();
+        let t2 = t.clone();
+        let d1 = t1.take().unwrap();
+        assert_eq!(*d1, 10);
+        assert_eq!(t1.read(), None);
+        let d2 = t2.write(42);
+        assert!(d2.is_none());
+        let d3 = t2.read().unwrap();
+        assert_eq!(*d3, 42);
+    }
+
+    #[test]
+    fn test_arc_eq() {
+        let t = RcuCell::new(10);
+        let v = t.read().unwrap();
+        assert!(t.arc_eq(&v));
+        t.write(11);
+        assert!(!t.arc_eq(&v));
+        let t1 = RcuCell::from(v.clone());
+        assert!(t1.arc_eq(&v));
+        let v2 = t.write(v);
+        let t2 = RcuCell::from(v2.clone());
+        assert!(RcuCell::ptr_eq(&t, &t1));
+        assert!(t2.arc_eq(v2.as_ref().unwrap()));
+    }
+
+    #[test]
+    fn cas_test() {
+        use super::ArcPointer;
+        use Ordering::SeqCst;
+
+        let a = RcuCell::new(1234);
+
+        let curr = a.read().as_ptr();
+        let res1 = unsafe { a.compare_exchange(curr, None, SeqCst, SeqCst) }.unwrap();
+        assert_eq!(res1, curr);
+        assert!(a.is_none());
+        let res2 = unsafe { a.compare_exchange(res1, Some(&Arc::new(5678)), SeqCst, SeqCst) };
+        assert!(res2.is_err());
+
+        let null = core::ptr::null();
+        let res2 = unsafe { a.compare_exchange(null, Some(&Arc::new(5678)), SeqCst, SeqCst) };
+        assert!(res2.is_ok());
+        assert_eq!(a.read().map(|v| *v), Some(5678));
+    }
+
+    #[test]
+    fn scoped_cell() {
+        extern crate std;
+
+        let hello = alloc::string::String::from("hello");
+        let world = alloc::string::String::from("world");
+        let cell = super::ScopedRcuCell::new(hello.as_str());
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let v = cell.read().unwrap();
+                assert!(*v == "hello" || *v == "world");
+            });
+            cell.write(world.as_str());
+        });
+        assert_eq!(*cell.read().unwrap(), "world");
+        cell.update(|v| v.map(|_| hello.as_str()));
+        assert_eq!(cell.into_inner(), Some("hello"));
+
+        // a leaked snapshot leaks the value instead of panicking
+        let cell = super::ScopedRcuCell::new(world.as_str());
+        core::mem::forget(cell.read());
+        assert_eq!(cell.into_inner(), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        let t = RcuCell::new(10u64);
+        let report = t.validate();
+        assert!(report.is_valid());
+        assert_eq!(report.addr, Arc::as_ptr(&t.read().unwrap()).addr());
+        assert_eq!(report.readers, 0);
+        assert!(!report.update_locked);
+        t.update(|_| {
+            assert!(t.validate().update_locked);
+            Some(11)
+        });
+        assert!(RcuCell::<u64>::none().validate().is_valid());
+    }
+
+    #[test]
+    fn test_fn_cell() {
+        let f = super::RcuFnCell::new(|x: u32| x + 1);
+        assert_eq!(f.call(1), Some(2));
+        f.write(|x| x * 10);
+        assert_eq!(f.call(2), Some(20));
+        // a shared callback can be installed in several cells
+        let shared: Arc<dyn Fn(u32) -> u32 + Send + Sync> = Arc::new(|x| x + 100);
+        let g = super::RcuFnCell::from_arc(shared.clone());
+        assert!(f.set(Some(shared.clone())).is_some());
+        assert_eq!(f.call(1), Some(101));
+        assert_eq!(g.call(2), Some(102));
+        assert!(Arc::ptr_eq(&f.read().unwrap(), &shared));
+        f.take();
+        assert!(f.is_none());
+        assert_eq!(f.call(3), None);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_hash_map() {
+        extern crate std;
+        use super::map::RcuHashMap;
+        use core::sync::atomic::AtomicBool;
+        use std::string::ToString;
+        use std::vec::Vec;
+
+        let map = RcuHashMap::with_capacity(2);
+        assert!(map.is_empty());
+        assert!(map.insert("a".to_string(), 1).is_none());
+        assert!(map.insert("b".to_string(), 2).is_none());
+        assert_eq!(map.get("a").as_deref(), Some(&1));
+        assert!(map.contains_key("b"));
+        assert!(map.get("c").is_none());
+        assert_eq!(map.insert("a".to_string(), 3).as_deref(), Some(&1));
+        assert_eq!(map.len(), 2);
+        assert!(map.remove("c").is_none());
+        assert_eq!(map.remove("a").as_deref(), Some(&3));
+        assert!(map.get("a").is_none());
+        assert_eq!(map.len(), 1);
+        map.clear();
+        assert!(map.is_empty());
+
+        let map = RcuHashMap::<usize, usize>::new();
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let map = &map;
+                s.spawn(move || {
+                    for i in 0..1000 {
+                        map.insert(t * 1000 + i, i);
+                        assert_eq!(map.get(&(t * 1000 + i)).as_deref(), Some(&i));
+                    }
+                    for i in (0..1000).step_by(2) {
+                        assert_eq!(map.remove(&(t * 1000 + i)).as_deref(), Some(&i));
+                    }
+                });
+            }
+        });
+        assert_eq!(map.len(), 2000);
+        let mut keys: Vec<_> = map.iter().map(|(k, _)| *k).collect();
+        keys.sort();
+        assert_eq!(keys.len(), 2000);
+        assert!(keys.iter().all(|k| k % 2 == 1));
+
+        // a racing clear never takes the count below zero
+        let map = RcuHashMap::<usize, usize>::with_capacity(4);
+        let stop = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..100000 {
+                    map.insert(i % 16, i);
+                }
+                stop.store(true, Ordering::Relaxed);
+            });
+            s.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    assert!(map.len() <= 16);
+                }
+            });
+            while !stop.load(Ordering::Relaxed) {
+                map.clear();
+            }
+        });
+        assert!(map.len() <= 16);
+    }
+
+    #[test]
+    fn test_skip_map() {
+        extern crate std;
+        use super::skip_map::RcuSkipMap;
+        use alloc::string::{String, ToString};
+        use alloc::vec::Vec;
+        use core::ops::Bound;
+        use core::sync::atomic::AtomicBool;
+
+        let map = RcuSkipMap::new();
+        assert!(map.is_empty());
+        for k in [5, 1, 3, 4, 2] {
+            assert!(map.insert(k.to_string(), k).is_none());
+        }
+        assert_eq!(map.get("3").as_deref(), Some(&3));
+        assert!(map.contains_key("5"));
+        assert!(map.get("6").is_none());
+        assert_eq!(map.insert("3".to_string(), 30).as_deref(), Some(&3));
+        assert_eq!(map.len(), 5);
+        assert_eq!(map.remove("1").as_deref(), Some(&1));
+        assert!(map.remove("1").is_none());
+        let keys: Vec<String> = map.iter().map(|e| e.key().clone()).collect();
+        assert_eq!(keys, ["2", "3", "4", "5"]);
+        let range = (Bound::Included("3"), Bound::Excluded("5"));
+        let values: Vec<i32> = map.range::<str, _>(range).map(|e| **e.value()).collect();
+        assert_eq!(values, [30, 4]);
+        let range = (Bound::Excluded("2"), Bound::Unbounded);
+        assert_eq!(map.range::<str, _>(range).count(), 3);
+        assert_eq!(map.len(), 4);
+
+        // the readers walk the list while the writers insert and remove
+        let map = RcuSkipMap::<usize, usize>::new();
+        let stop = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    let keys: Vec<_> = map.iter().map(|e| *e.key()).collect();
+                    assert!(keys.windows(2).all(|w| w[0] < w[1]));
+                }
+            });
+            let writers: Vec<_> = (0..4)
+                .map(|t| {
+                    let map = &map;
+                    s.spawn(move || {
+                        for i in 0..1000 {
+                            map.insert(t * 1000 + i, i);
+                            assert_eq!(map.get(&(t * 1000 + i)).as_deref(), Some(&i));
+                        }
+                        for i in (0..1000).step_by(2) {
+                            assert_eq!(map.remove(&(t * 1000 + i)).as_deref(), Some(&i));
+                        }
+                    })
+                })
+                .collect();
+            writers.into_iter().for_each(|w| w.join().unwrap());
+            stop.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(map.len(), 2000);
+        let keys: Vec<_> = map.range(1000..2000).map(|e| *e.key()).collect();
+        assert_eq!(keys.len(), 500);
+        assert!(keys.iter().all(|k| k % 2 == 1));
+
+        // a long list is dropped without a deep recursion
+        let map = RcuSkipMap::new();
+        (0..100_000).for_each(|i| drop(map.insert(i, ())));
+        drop(map);
+    }
+
+    #[test]
+    fn test_vec() {
+        extern crate std;
+        use super::RcuVec;
+        use std::vec;
+
+        let v = RcuVec::from(vec![1, 2, 3]);
+        let snapshot = v.load();
+        v.push(4);
+        assert_eq!(v.len(), 4);
+        assert_eq!(snapshot.len(), 3);
+        assert_eq!(v.remove(0).as_deref(), Some(&1));
+        assert!(v.remove(3).is_none());
+        assert_eq!(v.set(0, 5).as_deref(), Some(&2));
+        assert!(v.set(3, 6).is_none());
+        assert_eq!(v.get(0).as_deref(), Some(&5));
+        // the elements are shared by the snapshots
+        assert!(Arc::ptr_eq(&snapshot[2], &v.load()[1]));
+        let snapshot = v.load();
+        v.retain(|_| true);
+        assert!(Arc::ptr_eq(&snapshot, &v.load()));
+        v.retain(|x| x % 2 == 0);
+        assert_eq!(std::format!("{v:?}"), "[4]");
+        v.clear();
+        assert!(v.is_empty());
+
+        let v = RcuVec::new();
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let v = &v;
+                s.spawn(move || {
+                    for i in 0..100 {
+                        v.push(t * 100 + i);
+                        assert!(v.load().iter().any(|x| **x == t * 100 + i));
+                    }
+                });
+            }
+        });
+        assert_eq!(v.len(), 400);
+    }
+
+    #[test]
+    fn test_array() {
+        extern crate std;
+        use super::RcuArray;
+        use core::sync::atomic::AtomicBool;
+
+        let array = RcuArray::from([0, 0, 0]);
+        assert_eq!(array.len(), 3);
+        let stop = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    let [a, b, c] = array.snapshot();
+                    assert_eq!(a, b);
+                    assert_eq!(b, c);
+                }
+            });
+            for i in 1..1000 {
+                array.update(|values| values.fill(Some(Arc::new(i))));
+            }
+            stop.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(array.write(1, 42).as_deref(), Some(&999));
+        assert_eq!(array.read(1).as_deref(), Some(&42));
+        assert!(RcuArray::<u8, 2>::new()
+            .snapshot()
+            .iter()
+            .all(Option::is_none));
+    }
+
+    #[test]
+    fn test_stack() {
+        extern crate std;
+        use super::RcuStack;
+
+        let stack = RcuStack::new();
+        assert!(stack.pop().is_none());
+        stack.push(1);
+        stack.push(2);
+        assert_eq!(stack.peek().as_deref(), Some(&2));
+        assert_eq!(stack.pop().as_deref(), Some(&2));
+        assert_eq!(stack.pop().as_deref(), Some(&1));
+        assert!(stack.is_empty());
+
+        let popped = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for i in 0..1000 {
+                        stack.push(i);
+                        if stack.pop().is_some() {
+                            popped.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(popped.load(Ordering::Relaxed), 4000);
+        assert!(stack.is_empty());
+
+        // a long stack is dropped without recursion
+        let stack = RcuStack::new();
+        for i in 0..1_000_000 {
+            stack.push(i);
+        }
+        drop(stack);
+    }
+
+    #[test]
+    fn test_queue() {
+        extern crate std;
+        use super::RcuQueue;
+        use alloc::vec::Vec;
+
+        let queue = RcuQueue::new();
+        assert!(queue.pop().is_none());
+        queue.push(1);
+        queue.push(2);
+        assert!(!queue.is_empty());
+        assert_eq!(queue.pop(), Some(1));
+        queue.push(3);
+        assert_eq!(queue.pop(), Some(2));
+        assert_eq!(queue.pop(), Some(3));
+        assert!(queue.pop().is_none());
+        assert!(queue.is_empty());
+
+        // every value is popped once, in the order of its producer
+        let queue = RcuQueue::new();
+        let popped: Vec<Vec<(usize, usize)>> = std::thread::scope(|s| {
+            for t in 0..2 {
+                let queue = &queue;
+                s.spawn(move || (0..1000).for_each(|i| queue.push((t, i))));
+            }
+            let consumers: Vec<_> = (0..2)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut popped = Vec::new();
+                        while popped.len() < 1000 {
+                            if let Some(v) = queue.pop() {
+                                popped.push(v);
+                            }
+                        }
+                        popped
+                    })
+                })
+                .collect();
+            consumers.into_iter().map(|c| c.join().unwrap()).collect()
+        });
+        for popped in &popped {
+            for t in 0..2 {
+                let seq: Vec<_> = popped.iter().filter(|v| v.0 == t).collect();
+                assert!(seq.windows(2).all(|w| w[0].1 < w[1].1));
+            }
+        }
+        assert_eq!(popped.iter().map(Vec::len).sum::<usize>(), 2000);
+        assert!(queue.is_empty());
+
+        // a long queue is dropped without recursion
+        let queue = RcuQueue::new();
+        for i in 0..1_000_000 {
+            queue.push(i);
+        }
+        drop(queue);
+    }
+
+    #[test]
+    fn test_slab() {
+        extern crate std;
+        use super::RcuSlab;
+        use std::vec::Vec;
+
+        let slab = RcuSlab::new();
+        let a = slab.insert(1);
+        let b = slab.insert(2);
+        assert_eq!(slab.len(), 2);
+        assert_eq!(slab.get(a).as_deref(), Some(&1));
+        assert_eq!(slab.replace(b, 3).as_deref(), Some(&2));
+        assert_eq!(slab.remove(a).as_deref(), Some(&1));
+        assert!(slab.remove(a).is_none());
+        assert!(slab.replace(a, 4).is_none());
+        // the slot is reused, but the stale key doesn't match the new value
+        let c = slab.insert(5);
+        assert_eq!(c.index(), a.index());
+        assert!(slab.get(a).is_none());
+        assert_eq!(slab.get(c).as_deref(), Some(&5));
+        assert_eq!(slab.capacity(), 2);
+
+        let slab = RcuSlab::new();
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let slab = &slab;
+                s.spawn(move || {
+                    let keys: Vec<_> = (0..1000).map(|i| (slab.insert(t * 1000 + i), i)).collect();
+                    for &(key, i) in &keys {
+                        assert_eq!(slab.get(key).as_deref(), Some(&(t * 1000 + i)));
+                        if i % 2 == 0 {
+                            assert!(slab.remove(key).is_some());
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(slab.len(), 2000);
+        assert!(slab.capacity() <= 4000);
+    }
+
+    #[test]
+    #[cfg(feature = "hazard")]
+    fn test_hazard() {
+        extern crate std;
+        use super::hazard;
+
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+        #[derive(Debug)]
+        struct Value(usize);
+        impl Drop for Value {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let cell = hazard::RcuCell::some(Value(1));
+        let guard = cell.read().unwrap();
+        cell.write(Value(2));
+        hazard::reclaim();
+        // the guard protects the old value
+        assert_eq!(guard.0, 1);
+        assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+        drop(guard);
+        hazard::reclaim();
+        assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+        cell.update(|v| v.map(|v| Value(v.0 + 1)));
+        assert_eq!(cell.read().unwrap().0, 3);
+        // a panicking writer still releases the writer lock
+        let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            cell.update(|_| panic!("boom"))
+        }));
+        assert!(r.is_err());
+        cell.take();
+        assert!(cell.read().is_none());
+        hazard::reclaim();
+        assert_eq!(DROPS.load(Ordering::Relaxed), 3);
+
+        let cell = hazard::RcuCell::some(Value(0));
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        cell.update(|v| v.map(|v| Value(v.0 + 1)));
+                        assert!(cell.read().unwrap().0 > 0);
+                    }
+                });
+            }
+        });
+        assert_eq!(cell.into_inner().unwrap().0, 4000);
+        hazard::reclaim();
+        assert_eq!(DROPS.load(Ordering::Relaxed), 4000 + 4);
+    }
+
+    #[test]
+    fn test_cell_ops() {
+        use super::RcuCellOps;
+
+        fn bump<C: RcuCellOps<u32>>(cell: &C) -> u32 {
+            cell.update(|v| Some(v.map_or(0, |v| v + 1)));
+            let v = *cell.read().unwrap();
+            cell.take();
+            assert!(cell.read().is_none());
+            cell.write(v);
+            v
+        }
+
+        let cell = RcuCell::new(1);
+        assert_eq!(bump(&cell), 2);
+        assert_eq!(cell.read().as_deref(), Some(&2));
+        #[cfg(feature = "hazard")]
+        {
+            let cell = super::hazard::RcuCell::new(1);
+            assert_eq!(bump(&cell), 2);
+            assert_eq!(cell.read().as_deref(), Some(&2));
+        }
+    }
+
+    #[test]
+    fn test_cell_of() {
+        use super::RcuCellOf;
+
+        let cell = RcuCellOf::<i32, Option<Arc<i32>>>::from(Some(Arc::new(1)));
+        let v = cell.read().unwrap();
+        assert_eq!(*v, 1);
+        assert_eq!(cell.set(Some(Arc::new(2))), Some(v));
+        assert_eq!(
+            cell.update(|v| v.map(|v| Arc::new(*v + 1))).as_deref(),
+            Some(&2)
+        );
+        assert_eq!(cell.version(), 2);
+        assert_eq!(cell.take().as_deref(), Some(&3));
+        assert!(cell.is_none());
+
+        #[cfg(feature = "triomphe")]
+        {
+            extern crate std;
+            type TArc = triomphe::Arc<usize>;
+
+            let cell = RcuCellOf::<usize, Option<TArc>>::from(Some(TArc::new(0)));
+            std::thread::scope(|s| {
+                for _ in 0..4 {
+                    s.spawn(|| {
+                        for _ in 0..1000 {
+                            cell.update(|v| v.map(|v| TArc::new(*v + 1)));
+                            assert!(cell.read().is_some());
+                        }
+                    });
+                }
+            });
+            let v = cell.into_pointer().unwrap();
+            assert_eq!(*v, 4000);
+            assert!(v.is_unique());
+        }
+
+        // a misaligned pointer is rejected before it reaches the packed link
+        #[cfg(all(
+            feature = "std",
+            target_pointer_width = "64",
+            not(feature = "two-word")
+        ))]
+        {
+            extern crate std;
+            use super::{ArcPointer, RcuPointer};
+
+            #[derive(Clone)]
+            struct Misaligned;
+            impl ArcPointer<u32> for Misaligned {
+                fn as_ptr(&self) -> *const u32 {
+                    core::ptr::null::<u32>().wrapping_add(1)
+                }
+                fn into_raw(self) -> *const u32 {
+                    self.as_ptr()
+                }
+                unsafe fn from_raw(_ptr: *const u32) -> Self {
+                    Misaligned
+                }
+            }
+            unsafe impl RcuPointer<u32> for Misaligned {}
+
+            let r = std::panic::catch_unwind(|| RcuCellOf::<u32, Misaligned>::from(Misaligned));
+            assert!(r.is_err());
+        }
+    }
+
+    #[test]
+    fn test_rcu_box() {
+        extern crate std;
+        use super::RcuBox;
+        use alloc::boxed::Box;
+        use core::sync::atomic::AtomicBool;
+
+        let b = RcuBox::new(1u8);
+        assert_eq!(b.read_with(|v| v.copied()), Some(1));
+        assert_eq!(b.swap(2), Some(Box::new(1)));
+        assert_eq!(b.version(), 1);
+        assert_eq!(b.take(), Some(Box::new(2)));
+        assert!(b.is_none());
+        assert!(b.read_with(|v| v.is_none()));
+        let mut b = RcuBox::from(Box::new(3u64));
+        *b.get_mut().unwrap() += 1;
+        assert_eq!(b.into_inner(), Some(Box::new(4)));
+
+        // a panicking reader still releases its count for the writers
+        let b = RcuBox::new(5u8);
+        let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            b.read_with(|_| panic!("boom"))
+        }));
+        assert!(r.is_err());
+        assert_eq!(b.swap(6), Some(Box::new(5)));
+
+        let b = RcuBox::new([0u8; 3]);
+        let stop = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            for _ in 0..2 {
+                s.spawn(|| {
+                    while !stop.load(Ordering::Relaxed) {
+                        // the value is never changed under a reader
+                        b.read_with(|v| {
+                            let [x, y, z] = *v.unwrap();
+                            assert!(x == y && y == z);
+                        });
+                    }
+                });
+            }
+            for i in 1..=100 {
+                let old = b.swap([i; 3]).unwrap();
+                assert_eq!(*old, [i - 1; 3]);
+            }
+            stop.store(true, Ordering::Relaxed);
+        });
+    }
+
+    #[test]
+    fn test_unsized() {
+        use super::RcuUnsized;
+        use alloc::boxed::Box;
+        use alloc::string::{String, ToString};
+
+        trait Handler: Send + Sync {
+            fn handle(&self) -> String;
+        }
+        struct Hello;
+        impl Handler for Hello {
+            fn handle(&self) -> String {
+                "hello".to_string()
+            }
+        }
+        struct Bye;
+        impl Handler for Bye {
+            fn handle(&self) -> String {
+                "bye".to_string()
+            }
+        }
+
+        let handler = RcuUnsized::<dyn Handler>::new(Arc::new(Hello) as Arc<dyn Handler>);
+        assert_eq!(handler.read().unwrap().handle(), "hello");
+        let old = handler.write(Box::new(Bye) as Box<dyn Handler>).unwrap();
+        assert_eq!(old.handle(), "hello");
+        assert_eq!(handler.read().unwrap().handle(), "bye");
+
+        let slice = RcuUnsized::<[u8]>::new(&[1, 2][..]);
+        slice.update(|v| v.map(|v| [&*v, &[3]].concat()));
+        assert_eq!(&*slice.read().unwrap(), &[1, 2, 3]);
+        assert_eq!(slice.version(), 1);
+        assert_eq!(&*slice.take().unwrap(), &[1, 2, 3]);
+        assert!(slice.is_none());
+        let s = RcuUnsized::<str>::new("a");
+        assert_eq!(&*s.into_arc().unwrap(), "a");
+    }
+
+    #[test]
+    fn test_str_and_bytes() {
+        extern crate std;
+        use super::{RcuBytes, RcuStr};
+        use alloc::format;
+        use alloc::string::String;
+
+        let token = RcuStr::from("abc");
+        assert_eq!(&*token.read(), "abc");
+        assert_eq!(&*token.write(String::from("def")), "abc");
+        assert_eq!(token.load_ref(str::len), 3);
+        assert_eq!(format!("{token} {token:?}"), "def RcuStr(\"def\")");
+        assert_eq!(token.version(), 1);
+        assert_eq!(&*RcuStr::default().read(), "");
+
+        let payload = RcuBytes::from(alloc::vec![1, 2]);
+        assert_eq!(&*payload.write(&b"xyz"[..]), &[1, 2]);
+        assert!(payload.load_ref(|b| b.starts_with(b"xy")));
+        assert_eq!(&*payload.read(), b"xyz");
+        assert!(RcuBytes::default().load_ref(<[u8]>::is_empty));
+
+        // a panicking reader still releases its count
+        let r = std::panic::catch_unwind(|| token.load_ref(|_| panic!("boom")));
+        assert!(r.is_err());
+        let old = token.write("ghi");
+        assert_eq!(Arc::strong_count(&old), 1);
+    }
+
+    #[test]
+    fn test_value() {
+        extern crate std;
+        use super::RcuValue;
+        use core::sync::atomic::AtomicBool;
+
+        let v = RcuValue::new((0u64, 0u64));
+        assert_eq!(v.swap((1, 1)), (0, 0));
+        assert_eq!(v.update(|(a, b)| (a + 1, b + 1)), (1, 1));
+        assert_eq!(v.load(), (2, 2));
+        assert_eq!(v.version(), 2);
+        // a panic in the closure leaves the value untouched
+        let r = std::panic::catch_unwind(|| v.update(|_| panic!("boom")));
+        assert!(r.is_err());
+        assert_eq!(v.load(), (2, 2));
+
+        let stop = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    let (a, b) = v.load();
+                    assert_eq!(a, b);
+                }
+            });
+            std::thread::scope(|s| {
+                for _ in 0..2 {
+                    s.spawn(|| {
+                        for _ in 0..1000 {
+                            v.update(|(a, b)| (a + 1, b + 1));
+                        }
+                    });
+                }
+            });
+            stop.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(v.into_inner(), (2002, 2002));
+    }
+
+    #[test]
+    fn test_triple_buffer() {
+        extern crate std;
+        use super::triple::TripleBuffer;
+        use alloc::format;
+
+        // only the metadata is printed, never a buffer shared with a writer
+        let t = TripleBuffer::new(core::cell::RefCell::new(0));
+        assert_eq!(format!("{t:?}"), "TripleBuffer { middle: 1, dirty: false }");
+
+        let (mut input, mut output) = TripleBuffer::new([0usize; 4]).split();
+        assert!(!output.updated());
+        assert!(!input.write([1; 4]));
+        assert!(input.write([2; 4]));
+        assert_eq!(*output.read(), [2; 4]);
+        assert!(input.consumed());
+        // the back buffer holds a stale value, fill it in place
+        input.input_buffer().fill(3);
+        input.publish();
+        assert_eq!(*output.read(), [3; 4]);
+
+        std::thread::scope(|s| {
+            s.spawn(move || {
+                for i in 4..10000 {
+                    input.write([i; 4]);
+                }
+            });
+            let mut last = 0;
+            while last != 9999 {
+                let v = *output.read();
+                assert!(v.iter().all(|x| *x == v[0]));
+                assert!(v[0] >= last);
+                last = v[0];
+            }
+        });
+    }
+
+    #[test]
+    fn test_deferred_drop() {
+        use core::sync::atomic::AtomicBool;
+
+        static DROPPED: AtomicBool = AtomicBool::new(false);
+        struct Heavy;
+        impl Drop for Heavy {
+            fn drop(&mut self) {
+                DROPPED.store(true, Ordering::Relaxed);
+            }
+        }
+
+        let cell = RcuCell::new(Heavy);
+        cell.write_deferred(Heavy);
+        assert!(!DROPPED.load(Ordering::Relaxed));
+        assert!(super::collect() >= 1);
+        assert!(DROPPED.load(Ordering::Relaxed));
+
+        let collector = super::Collector::new();
+        assert!(collector.is_empty());
+        collector.defer(Arc::new(1));
+        collector.defer(alloc::string::String::from("old"));
+        assert!(!collector.is_empty());
+        assert_eq!(collector.collect(), 2);
+        assert_eq!(collector.collect(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_background_drop() {
+        extern crate std;
+        use std::sync::Mutex;
+        use std::thread::{self, ThreadId};
+
+        static DROPPED_ON: Mutex<Option<ThreadId>> = Mutex::new(None);
+        struct Heavy(usize);
+        impl Drop for Heavy {
+            fn drop(&mut self) {
+                *DROPPED_ON.lock().unwrap() = Some(thread::current().id());
+            }
+        }
+
+        let cell = RcuCell::new(Heavy(1)).with_background_drop();
+        let r = cell.read().unwrap();
+        assert_eq!(r.0, 1);
+        // the reader holds the last reference after the write
+        let old = cell.write(Heavy(2)).unwrap();
+        drop(old);
+        assert!(DROPPED_ON.lock().unwrap().is_none());
+        drop(r);
+        let dropped_on = loop {
+            if let Some(id) = *DROPPED_ON.lock().unwrap() {
+                break id;
+            }
+            thread::yield_now();
+        };
+        assert_ne!(dropped_on, thread::current().id());
+
+        let a = cell.read().unwrap().into_arc();
+        let cell = cell.into_inner();
+        assert!(Arc::ptr_eq(&a, &cell.read().unwrap()));
+    }
+
+    #[test]
+    fn test_update_unwind() {
+        extern crate std;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let t = RcuCell::new(1);
+        let r = catch_unwind(AssertUnwindSafe(|| {
+            t.update(|_| -> Option<i32> { panic!("boom") });
+        }));
+        assert!(r.is_err());
+        // the old value is left in place and the writers are not locked out
+        assert_eq!(t.read().map(|v| *v), Some(1));
+        assert_eq!(t.write(2).map(|v| *v), Some(1));
+        let old = t.update(|v| v.map(|v| *v + 1));
+        assert_eq!(old.map(|v| *v), Some(2));
+
+        let r = catch_unwind(AssertUnwindSafe(|| {
+            t.update_mut(|v| {
+                *v += 1;
+                panic!("boom");
+            });
+        }));
+        assert!(r.is_err());
+        // the partly mutated value is published again for the readers
+        assert_eq!(t.read().map(|v| *v), Some(4));
+        assert!(t.update_mut(|v| *v += 1));
+        assert_eq!(t.read().map(|v| *v), Some(5));
+
+        let w = super::RcuWeak::new();
+        let r = catch_unwind(AssertUnwindSafe(|| {
+            w.update(|_| panic!("boom"));
+        }));
+        assert!(r.is_err());
+        let a = Arc::new(1);
+        assert!(w.write_arc(&a).upgrade().is_none());
+        assert!(w.arc_eq(&a));
+    }
+
+    #[test]
+    fn test_reader_count() {
+        extern crate std;
+        use std::sync::Barrier;
+
+        let t = RcuCell::new(1);
+        assert_eq!(t.reader_count(), 0);
+        assert!(!t.is_update_locked());
+
+        let barrier = Barrier::new(2);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                t.update(|v| {
+                    barrier.wait();
+                    barrier.wait();
+                    v
+                });
+            });
+            barrier.wait();
+            assert!(t.is_update_locked());
+            assert_eq!(t.reader_count(), 0);
+            barrier.wait();
+        });
+        assert!(!t.is_update_locked());
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn test_stats() {
+        extern crate std;
+        use std::sync::Barrier;
+
+        let t = RcuCell::new(1);
+        assert_eq!(t.stats(), super::ContentionStats::default());
+
+        let barrier = Barrier::new(2);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                t.update(|v| {
+                    barrier.wait();
+                    // let the writer find the update lock held
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                    v.map(|v| *v + 1)
+                });
+            });
+            barrier.wait();
+            t.write(10);
+        });
+        assert!(t.stats().lock_waits >= 1);
+        assert_eq!(t.read().map(|v| *v), Some(10));
+    }
+
+    #[test]
+    fn test_load_store_ordering() {
+        extern crate std;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let t = RcuCell::new(1);
+        assert_eq!(t.load(Ordering::Relaxed).map(|v| *v), Some(1));
+        t.store(Some(Arc::new(2)), Ordering::Relaxed);
+        assert_eq!(t.load(Ordering::Acquire).map(|v| *v), Some(2));
+        t.store(None, Ordering::SeqCst);
+        assert!(t.load(Ordering::SeqCst).is_none());
+        t.store(Some(Arc::new(3)), Ordering::Release);
+        assert_eq!(t.read().map(|v| *v), Some(3));
+
+        let r = catch_unwind(AssertUnwindSafe(|| t.load(Ordering::Release)));
+        assert!(r.is_err());
+        let r = catch_unwind(AssertUnwindSafe(|| t.store(None, Ordering::Acquire)));
+        assert!(r.is_err());
+        // the panicking store leaves the value untouched
+        assert_eq!(t.read().map(|v| *v), Some(3));
+    }
+
+    #[test]
+    fn test_as_ptr() {
+        let t = RcuCell::<i32>::none();
+        assert!(t.as_ptr().is_null());
+        let a = Arc::new(1);
+        t.set(Some(a.clone()));
+        assert_eq!(t.as_ptr(), Arc::as_ptr(&a));
+        let last = t.as_ptr();
+        t.write(2);
+        assert_ne!(t.as_ptr(), last);
+    }
+
+    #[test]
+    fn test_swap_if_arc_eq() {
+        let t = RcuCell::new(1);
+        let expected = t.read().unwrap();
+        let derived = Arc::new(*expected + 1);
+        let old = t.swap_if_arc_eq(&expected, Some(derived.clone()));
+        assert!(Arc::ptr_eq(&old.unwrap().unwrap(), &expected));
+        assert!(t.arc_eq(&derived));
+
+        // someone beat us to it, the new value is given back
+        let lost = t.swap_if_arc_eq(&expected, Some(Arc::new(10)));
+        assert_eq!(lost.unwrap_err().map(|v| *v), Some(10));
+        assert!(t.arc_eq(&derived));
+
+        assert_eq!(t.swap_if_arc_eq(&derived, None).unwrap(), Some(derived));
+        assert!(t.is_none());
+    }
+
+    #[test]
+    fn test_compare_exchange_weak() {
+        extern crate std;
+
+        let t = RcuCell::new(0usize);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        loop {
+                            let cur = t.read().unwrap();
+                            let new = Some(Arc::new(*cur + 1));
+                            if t.swap_if_arc_eq_weak(&cur, new).is_ok() {
+                                break;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(t.read().map(|v| *v), Some(4000));
+
+        let cur = t.read();
+        let new = Arc::new(0);
+        loop {
+            let ptr = cur.as_ref().map_or(core::ptr::null(), Arc::as_ptr);
+            let ret = unsafe {
+                t.compare_exchange_weak(ptr, Some(&new), Ordering::AcqRel, Ordering::Acquire)
+            };
+            if let Err(actual) = ret {
+                assert_eq!(actual, ptr);
+                continue;
+            }
+            break;
+        }
+        assert!(t.arc_eq(&new));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_wait_for_some() {
+        extern crate std;
+        use std::time::Duration;
+
+        let t = RcuCell::none();
+        assert_eq!(t.wait_for_some(Some(Duration::from_millis(10))), None);
+        assert_eq!(t.wait_for_some(Some(Duration::ZERO)), None);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(Duration::from_millis(20));
+                t.write(1);
+            });
+            let v = t.wait_for_some(Some(Duration::from_secs(10)));
+            assert_eq!(v.map(|v| *v), Some(1));
+        });
+        assert_eq!(t.wait_for_some(None).map(|v| *v), Some(1));
+        assert_eq!(t.wait_for_some(Some(Duration::MAX)).map(|v| *v), Some(1));
+
+        t.take();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(Duration::from_millis(20));
+                t.write(2);
+            });
+            assert_eq!(t.wait_for_some(None).map(|v| *v), Some(2));
+        });
+    }
+
+    #[test]
+    // `LazyRcuCell::new` is not const under loom
+    #[cfg(not(loom))]
+    fn test_rcu_static() {
+        extern crate std;
+
+        static INITS: AtomicUsize = AtomicUsize::new(0);
+        crate::rcu_static! {
+            static CELL: usize = INITS.fetch_add(1, Ordering::Relaxed) + 10;
+        }
+
+        assert!(!CELL.is_initialized());
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| assert!(CELL.read().is_some()));
+            }
+        });
+        assert!(CELL.is_initialized());
+        assert_eq!(CELL.read().map(|v| *v), Some(10));
+        CELL.write(20);
+        assert_eq!(CELL.read().map(|v| *v), Some(20));
+        CELL.take();
+        assert!(CELL.is_none());
+        assert_eq!(INITS.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "hazard")]
+    fn test_hazard_guard_map() {
+        extern crate std;
+        use super::hazard::{self, Guard};
+        use alloc::string::String;
+
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+        struct Config {
+            name: String,
+            port: u16,
+        }
+        impl Drop for Config {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let cell = hazard::RcuCell::some(Config {
+            name: "rcu".into(),
+            port: 80,
+        });
+        let port = Guard::map(cell.read().unwrap(), |v| &v.port);
+        let name = Guard::map(cell.read().unwrap(), |v| &v.name);
+        let name = Guard::map(name, String::as_str);
+        cell.write(Config {
+            name: "cell".into(),
+            port: 8080,
+        });
+        hazard::reclaim();
+        // the mapped guards still protect the whole old value
+        assert_eq!(*port, 80);
+        assert_eq!(&*name, "rcu");
+        assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+        drop((port, name));
+        hazard::reclaim();
+        assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+        assert_eq!(*Guard::map(cell.read().unwrap(), |v| &v.port), 8080);
+    }
+
+    #[test]
+    fn test_read_clone() {
+        use alloc::string::String;
+
+        let t = RcuCell::new(String::from("rcu"));
+        let v = t.read_clone();
+        t.write(String::from("cell"));
+        assert_eq!(v.as_deref(), Some("rcu"));
+        assert_eq!(t.read_clone().as_deref(), Some("cell"));
+        // the clone doesn't keep the Arc alive
+        let arc = t.take().unwrap();
+        assert_eq!(Arc::strong_count(&arc), 1);
+        assert_eq!(t.read_clone(), None);
+    }
+
+    #[test]
+    fn test_get_or_init() {
+        extern crate std;
+        use alloc::vec::Vec;
+
+        let t = RcuCell::<usize>::none();
+        assert_eq!(*t.get_or_default(), 0);
+        assert_eq!(*t.get_or_init(|| 1), 0);
+        t.take();
+        assert_eq!(*t.get_or_init(|| 1), 1);
+
+        static INITS: AtomicUsize = AtomicUsize::new(0);
+        let t = RcuCell::<usize>::none();
+        let values: Vec<_> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4)
+                .map(|i| {
+                    let t = &t;
+                    s.spawn(move || {
+                        t.get_or_init(|| {
+                            INITS.fetch_add(1, Ordering::Relaxed);
+                            i
+                        })
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(INITS.load(Ordering::Relaxed), 1);
+        assert!(values.iter().all(|v| t.arc_eq(v)));
+    }
+
+    #[test]
+    fn test_write_if_none() {
+        extern crate std;
+
+        let t = RcuCell::none();
+        let v = t.write_if_none(1).unwrap();
+        assert!(t.arc_eq(&v));
+        let (rejected, cur) = t.write_if_none(2).unwrap_err();
+        assert_eq!((rejected, *cur), (2, 1));
+        t.take();
+        assert_eq!(t.write_if_none(3).map(|v| *v), Ok(3));
+
+        // exactly one of the racing writers claims the empty slot
+        let t = RcuCell::none();
+        let claimed = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for i in 0..4 {
+                let (t, claimed) = (&t, &claimed);
+                s.spawn(move || match t.write_if_none(i) {
+                    Ok(_) => {
+                        claimed.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err((v, cur)) => assert!(v == i && *cur != i),
+                });
+            }
+        });
+        assert_eq!(claimed.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_update_with_ref() {
+        extern crate std;
+
+        let t = RcuCell::new(10);
+        let old = t.update_with_ref(|v| v.map(|x| x + 1));
+        assert_eq!(old.map(|v| *v), Some(10));
+        assert_eq!(t.read().map(|v| *v), Some(11));
+        // the old Arc is handed out without a clone
+        let old = t.update_with_ref(|_| None::<i32>).unwrap();
+        assert_eq!(Arc::strong_count(&old), 1);
+        assert!(t.is_none());
+        assert_eq!(t.update_with_ref(|v| Some(v.map_or(0, |x| x + 1))), None);
+
+        let t = RcuCell::new(0);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        t.update_with_ref(|v| v.map(|x| x + 1));
+                    }
+                });
+            }
+        });
+        assert_eq!(t.read().map(|v| *v), Some(4000));
+    }
+
+    #[test]
+    fn test_upgrade_with() {
+        use super::RcuWeak;
+
+        let v = Arc::new(10);
+        let w = RcuWeak::new();
+        assert_eq!(w.upgrade_with(|v| v.copied()), None);
+        w.write_arc(&v);
+        assert_eq!(w.upgrade_with(|v| v.map(|x| x + 1)), Some(11));
+        // the temporary strong reference is already released
+        assert_eq!(Arc::strong_count(&v), 1);
+        drop(v);
+        assert!(w.upgrade_with(|v| v.is_none()));
+    }
+
+    #[test]
+    fn test_rcu_weak_raw() {
+        use super::RcuWeak;
+        use alloc::sync::Weak;
+        use core::ptr;
+
+        let a = Arc::new(1);
+        let b = Arc::new(2);
+        let w = RcuWeak::<i32>::new();
+        assert!(w.as_ptr().is_null());
+        w.write_arc(&a);
+        assert_eq!(w.as_ptr(), Arc::as_ptr(&a));
+
+        let ptr = w.into_raw();
+        assert_eq!(ptr, Arc::as_ptr(&a));
+        assert_eq!(Arc::weak_count(&a), 1);
+        let w = unsafe { RcuWeak::from_raw(ptr) };
+        assert!(w.arc_eq(&a));
+        assert!(RcuWeak::<i32>::new().into_raw().is_null());
+        let dangling = unsafe { RcuWeak::<i32>::from_raw(Weak::into_raw(Weak::new())) };
+        assert!(dangling.as_ptr().is_null());
+
+        let cur = w.as_ptr();
+        let new = Arc::downgrade(&b);
+        let ret = unsafe { w.compare_exchange_raw(cur, &new, Ordering::AcqRel, Ordering::Acquire) };
+        assert_eq!(ret, Ok(Arc::as_ptr(&a)));
+        assert_eq!(Arc::weak_count(&a), 0);
+        assert_eq!(Arc::weak_count(&b), 2);
+        let ret = unsafe {
+            w.compare_exchange_raw(cur, &Weak::new(), Ordering::AcqRel, Ordering::Acquire)
+        };
+        assert_eq!(ret, Err(Arc::as_ptr(&b)));
+        assert_eq!(Arc::weak_count(&b), 2);
+        let ret = unsafe {
+            w.compare_exchange_raw(
+                Arc::as_ptr(&b),
+                &Weak::new(),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+        };
+        assert!(ret.is_ok());
+        assert_eq!(w.as_ptr(), ptr::null());
+        assert_eq!(Arc::weak_count(&b), 1);
+    }
+
+    #[test]
+    fn test_weak_list() {
+        extern crate std;
+        use super::RcuWeakList;
+        use alloc::vec::Vec;
+
+        let list = RcuWeakList::new();
+        let subscribers: Vec<_> = (0..4).map(Arc::new).collect();
+        for s in &subscribers {
+            list.push(Arc::downgrade(s));
+        }
+        let mut seen = Vec::new();
+        assert_eq!(list.for_each_alive(|v| seen.push(*v)), 4);
+        assert_eq!(seen, [0, 1, 2, 3]);
+
+        // the dropped subscribers are pruned by the next traversal
+        let mut subscribers = subscribers;
+        subscribers.remove(1);
+        subscribers.remove(2);
+        assert_eq!(list.len(), 4);
+        seen.clear();
+        assert_eq!(list.for_each_alive(|v| seen.push(*v)), 2);
+        assert_eq!(seen, [0, 2]);
+        assert_eq!(list.len(), 2);
+
+        let sum = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..100 {
+                    let v = Arc::new(i);
+                    list.push(Arc::downgrade(&v));
+                }
+            });
+            s.spawn(|| {
+                for _ in 0..100 {
+                    list.for_each_alive(|v| {
+                        sum.fetch_add(*v, Ordering::Relaxed);
+                    });
+                }
+            });
+        });
+        // only the two subscribers still held are left
+        list.prune();
+        assert_eq!(list.len(), 2);
+        drop(subscribers);
+        assert_eq!(list.for_each_alive(|_| unreachable!()), 0);
+        assert!(list.is_empty());
     }
 }
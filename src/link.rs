@@ -1,181 +1,345 @@
 use core::fmt;
-use core::marker::PhantomData;
-use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(any(feature = "fair", feature = "stats"))]
+use core::sync::atomic::Ordering;
 
-const LEADING_BITS: usize = 8;
-const ALIGN_BITS: usize = 3;
+use crate::sync::const_fn;
+#[cfg(feature = "fair")]
+use crate::sync::AtomicUsize;
 
-const LOWER_MASK: usize = (1 << ALIGN_BITS) - 1;
-const HIGHER_MASK: usize = !((1 << (usize::MAX.leading_ones() as usize - LEADING_BITS)) - 1);
-const REFCOUNT_MASK: usize = (1 << (LEADING_BITS + ALIGN_BITS)) - 1;
-const UPDTATE_MASK: usize = 1 << (LEADING_BITS + ALIGN_BITS - 1);
-const UPDATE_REF_MASK: usize = REFCOUNT_MASK & !UPDTATE_MASK;
+// the packed layout keeps the pointer and the reader count in one word and
+// needs the spare bits of a 64-bit pointer, the portable layout keeps them
+// in two words and is used on all the other platforms, see `portable.rs`
+#[cfg(all(target_pointer_width = "64", not(feature = "two-word")))]
+mod packed;
+#[cfg(any(not(target_pointer_width = "64"), feature = "two-word"))]
+mod portable;
 
-#[repr(C)]
-union Ptr<T> {
-    addr: usize,
-    ptr: *const T,
+#[cfg(all(target_pointer_width = "64", not(feature = "two-word")))]
+pub(crate) use packed::{LinkWrapper, PTR_ALIGN};
+#[cfg(any(not(target_pointer_width = "64"), feature = "two-word"))]
+pub(crate) use portable::{LinkWrapper, PTR_ALIGN};
+
+// Credit `n` counts of the in-flight readers to the refcount of a swapped
+// out pointer, a negative `n` withdraws them, see `LinkWrapper::publish`.
+// A withdrawal never drops the last count. It's a type parameter instead of
+// a fn pointer, so the call is static and the `try_*` paths stay panic free.
+pub(crate) trait Credit<T> {
+    // the portable layout never credits the laggards
+    #[cfg_attr(
+        any(not(target_pointer_width = "64"), feature = "two-word"),
+        allow(dead_code)
+    )]
+    unsafe fn credit(&self, ptr: *const T, n: isize);
 }
 
-impl<T> Ptr<T> {
+// for the links whose readers don't hold a refcount, or are drained
+pub(crate) struct NoCredit;
+
+impl<T> Credit<T> for NoCredit {
     #[inline]
-    const fn addr(self) -> usize {
-        unsafe { self.addr }
+    unsafe fn credit(&self, _ptr: *const T, n: isize) {
+        debug_assert_eq!(n, 0);
     }
+}
 
+/// A snapshot of the link invariants, see `RcuCell::validate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Validation {
+    /// the decoded address of the inner data
+    pub addr: usize,
+    /// the number of in-flight readers
+    pub readers: usize,
+    /// whether the update flag is set
+    pub update_locked: bool,
+    /// the decoded address is not aligned for the inner type
+    pub misaligned: bool,
+    /// the reader count is saturated
+    pub readers_overflow: bool,
+}
+
+impl Validation {
+    /// check if no invariant is violated
     #[inline]
-    const fn ptr(self) -> *const T {
-        unsafe { self.ptr }
+    pub fn is_valid(&self) -> bool {
+        !self.misaligned && !self.readers_overflow
+    }
+}
+
+/// A decoded snapshot of the link, see `RcuCell::debug_state`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellState {
+    /// the decoded address of the inner data
+    pub addr: usize,
+    /// the number of in-flight readers
+    pub readers: usize,
+    /// whether an updater holds the update flag
+    pub updating: bool,
+    /// whether an updater is mutating the data in place
+    pub mutating: bool,
+    /// the generation of the last publication
+    pub generation: u64,
+}
+
+impl fmt::Display for CellState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ptr={:#x} readers={} updating={} mutating={} generation={}",
+            self.addr, self.readers, self.updating, self.mutating, self.generation
+        )
     }
 }
 
-/// A wrapper of the pointer to the inner Arc data
-pub(crate) struct LinkWrapper<T> {
-    ptr: AtomicUsize,
-    phantom: PhantomData<*const T>,
+/// An opaque identity of a published value, see `RcuCell::identity`
+///
+/// It's derived from the address and the generation of the publication,
+/// but the address is scrambled, so it's safe to log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ValueId {
+    generation: u64,
+    tag: u64,
 }
 
-impl<T> LinkWrapper<T> {
+impl ValueId {
+    // splitmix64 finalizer, just to not expose the address
     #[inline]
-    pub(crate) const fn new(ptr: *const T) -> Self {
-        let addr = Ptr { ptr }.addr();
-        debug_assert!(addr & LOWER_MASK == 0);
-        debug_assert!(addr & HIGHER_MASK == 0);
-        LinkWrapper {
-            ptr: AtomicUsize::new(addr << LEADING_BITS),
-            phantom: PhantomData,
+    pub(crate) fn new(addr: usize, generation: u64) -> Self {
+        let mut x = (addr as u64) ^ 0x9e37_79b9_7f4a_7c15;
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        ValueId {
+            generation,
+            tag: x ^ (x >> 31),
         }
     }
 
-    pub(crate) unsafe fn compare_exchange(
-        &self,
-        current: *const T,
-        new: *const T,
-        success: Ordering,
-        failure: Ordering,
-    ) -> Result<*const T, *const T> {
-        let new_addr = Ptr { ptr: new }.addr();
-        let new = new_addr << LEADING_BITS;
-
-        let old_addr = Ptr { ptr: current }.addr();
-        let old = old_addr << LEADING_BITS;
-
-        let backoff = crossbeam_utils::Backoff::new();
-        loop {
-            match self.ptr.compare_exchange(old, new, success, failure) {
-                Ok(_addr) => {
-                    // assert_eq!(old, addr);
-                    return Ok(current);
-                }
-                Err(addr) => {
-                    let addr = (addr & !REFCOUNT_MASK) >> LEADING_BITS;
-                    if addr != old {
-                        return Err(Ptr { addr }.ptr());
-                    }
-                    backoff.snooze();
-                }
-            }
+    /// the generation of the publication
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+impl fmt::Display for ValueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{:016x}", self.generation, self.tag)
+    }
+}
+
+/// The contention counters of a cell, see `RcuCell::stats`
+#[cfg(feature = "stats")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContentionStats {
+    /// the snoozes of the readers and the writers spinning on the cell
+    pub spins: u64,
+    /// the failed compare-and-swaps on the cell
+    pub cas_failures: u64,
+    /// the times a writer waited for the update lock or its turn
+    pub lock_waits: u64,
+}
+
+// the contention counters of a link with the `stats` feature,
+// otherwise the counting is a no-op
+pub(crate) struct Stats {
+    #[cfg(feature = "stats")]
+    spins: core::sync::atomic::AtomicUsize,
+    #[cfg(feature = "stats")]
+    cas_failures: core::sync::atomic::AtomicUsize,
+    #[cfg(feature = "stats")]
+    lock_waits: core::sync::atomic::AtomicUsize,
+}
+
+impl Stats {
+    #[inline]
+    const fn new() -> Self {
+        Stats {
+            #[cfg(feature = "stats")]
+            spins: core::sync::atomic::AtomicUsize::new(0),
+            #[cfg(feature = "stats")]
+            cas_failures: core::sync::atomic::AtomicUsize::new(0),
+            #[cfg(feature = "stats")]
+            lock_waits: core::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    fn spin(&self) {
+        #[cfg(feature = "stats")]
+        self.spins.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn cas_failed(&self) {
+        #[cfg(feature = "stats")]
+        self.cas_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn lock_wait(&self) {
+        #[cfg(feature = "stats")]
+        self.lock_waits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn snapshot(&self) -> ContentionStats {
+        ContentionStats {
+            spins: self.spins.load(Ordering::Relaxed) as u64,
+            cas_failures: self.cas_failures.load(Ordering::Relaxed) as u64,
+            lock_waits: self.lock_waits.load(Ordering::Relaxed) as u64,
         }
     }
+}
 
-    pub(crate) fn update(&self, ptr: *const T) -> *const T {
-        use Ordering::*;
-        let addr = Ptr { ptr }.addr();
-        debug_assert!(addr & LOWER_MASK == 0);
-        debug_assert!(addr & HIGHER_MASK == 0);
-        let new = addr << LEADING_BITS;
-        let mut old = self.ptr.load(Relaxed) & !REFCOUNT_MASK;
+// wait for the writer turn, with the `fair` feature the writers are
+// served in FIFO order by a ticket lock, otherwise they just race for
+// the update flag and this is a no-op
+struct Writers {
+    #[cfg(feature = "fair")]
+    next_ticket: AtomicUsize,
+    #[cfg(feature = "fair")]
+    now_serving: AtomicUsize,
+}
 
-        let backoff = crossbeam_utils::Backoff::new();
-        // wait all reader release
-        while let Err(addr) = self.ptr.compare_exchange_weak(old, new, Release, Relaxed) {
-            old = addr & !REFCOUNT_MASK;
-            backoff.snooze();
+impl Writers {
+    const_fn! {
+        #[inline]
+        const fn new() -> Self {
+            Writers {
+                #[cfg(feature = "fair")]
+                next_ticket: AtomicUsize::new(0),
+                #[cfg(feature = "fair")]
+                now_serving: AtomicUsize::new(0),
+            }
         }
+    }
 
-        core::sync::atomic::fence(Ordering::Acquire);
-        let addr = old >> LEADING_BITS;
-        Ptr { addr }.ptr()
-    }
-
-    // this is only used after lock_read
-    pub(crate) fn unlock_update(&self, ptr: *const T) -> *const T {
-        use Ordering::*;
-        let addr = Ptr { ptr }.addr();
-        debug_assert!(addr & LOWER_MASK == 0);
-        debug_assert!(addr & HIGHER_MASK == 0);
-        let new = addr << LEADING_BITS;
-        let mut old = self.ptr.load(Relaxed) & !UPDATE_REF_MASK | UPDTATE_MASK;
-
-        let backoff = crossbeam_utils::Backoff::new();
-        // wait all reader release
-        while let Err(addr) = self.ptr.compare_exchange_weak(old, new, Release, Relaxed) {
-            old = addr & !UPDATE_REF_MASK | UPDTATE_MASK;
-            backoff.snooze();
+    #[inline]
+    fn lock(&self, stats: &Stats) {
+        #[cfg(feature = "fair")]
+        {
+            let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+            let ready = || self.now_serving.load(Ordering::Acquire) == ticket;
+            if !ready() {
+                stats.lock_wait();
+                crate::park::wait_until(self.key(), ready);
+            }
         }
+        #[cfg(not(feature = "fair"))]
+        let _ = stats;
+    }
 
-        core::sync::atomic::fence(Ordering::Acquire);
-        let addr = (old & !UPDTATE_MASK) >> LEADING_BITS;
-        Ptr { addr }.ptr()
+    // take the writer turn only if nobody holds or waits for it
+    #[cfg(feature = "panic-free")]
+    #[inline]
+    fn try_lock(&self) -> bool {
+        #[cfg(feature = "fair")]
+        {
+            let ticket = self.now_serving.load(Ordering::Acquire);
+            self.next_ticket
+                .compare_exchange(ticket, ticket + 1, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        }
+        #[cfg(not(feature = "fair"))]
+        true
     }
 
     #[inline]
-    pub(crate) fn is_none(&self) -> bool {
-        self.ptr.load(Ordering::Relaxed) & !REFCOUNT_MASK == 0
+    fn unlock(&self) {
+        #[cfg(feature = "fair")]
+        {
+            self.now_serving.fetch_add(1, Ordering::Release);
+            crate::park::wake(self.key());
+        }
     }
 
+    // the parking key of the writers
+    #[cfg(feature = "fair")]
     #[inline]
-    pub(crate) fn inc_ref(&self) -> *const T {
-        let addr = self.ptr.fetch_add(1, Ordering::Acquire);
-        let refs = addr & REFCOUNT_MASK;
-        assert!(refs < REFCOUNT_MASK, "Too many references");
-        let addr = (addr & !REFCOUNT_MASK) >> LEADING_BITS;
-        Ptr { addr }.ptr()
+    fn key(&self) -> usize {
+        (self as *const Self).addr()
     }
+}
+
+// The update flag taken by `lock_read` for a writer that calls a closure
+// before publishing. If the closure panics the flag is released on unwind,
+// so the other writers are never locked out, and the old value is left in
+// place, republished if it's being mutated to let the readers in again.
+pub(crate) struct UpdateLock<'a, T> {
+    link: &'a LinkWrapper<T>,
+    ptr: *const T,
+    mutating: bool,
+}
 
+impl<'a, T> UpdateLock<'a, T> {
     #[inline]
-    pub(crate) fn get_ref(&self) -> *const T {
-        let addr = self.ptr.load(Ordering::Acquire);
-        let addr = (addr & !REFCOUNT_MASK) >> LEADING_BITS;
-        Ptr { addr }.ptr()
+    pub(crate) fn new(link: &'a LinkWrapper<T>) -> Self {
+        let ptr = link.lock_read();
+        UpdateLock {
+            link,
+            ptr,
+            mutating: false,
+        }
     }
 
+    // the pointer read by `lock_read`
     #[inline]
-    pub(crate) fn dec_ref(&self) {
-        self.ptr.fetch_sub(1, Ordering::Release);
+    pub(crate) fn ptr(&self) -> *const T {
+        self.ptr
     }
 
-    // read the inner Arc and increase the ref count
-    // to prevet other writer to update the inner Arc
-    // should be paired used with unlock_update
+    // see `LinkWrapper::lock_mut`
     #[inline]
-    pub(crate) fn lock_read(&self) -> *const T {
-        use Ordering::*;
+    pub(crate) fn lock_mut(&mut self) {
+        self.link.lock_mut();
+        self.mutating = true;
+    }
 
-        let addr = self.ptr.load(Relaxed);
-        let mut old = addr & !UPDTATE_MASK; // clear the update flag
-        let mut new = addr | UPDTATE_MASK; // set the update flag
+    // publish the new pointer, see `LinkWrapper::unlock_update`
+    #[inline]
+    pub(crate) fn unlock_update(self, ptr: *const T, credit: impl Credit<T>) -> (*const T, u64) {
+        let this = core::mem::ManuallyDrop::new(self);
+        this.link.unlock_update(ptr, credit)
+    }
 
-        let refs = old & UPDATE_REF_MASK;
-        assert!(refs < UPDATE_REF_MASK, "Too many references");
+    // release the update flag without publishing anything
+    #[inline]
+    pub(crate) fn unlock(self) {
+        drop(self);
+    }
+}
 
-        let backoff = crossbeam_utils::Backoff::new();
-        while let Err(addr) = self.ptr.compare_exchange_weak(old, new, Release, Relaxed) {
-            old = addr & !UPDTATE_MASK;
-            new = addr | UPDTATE_MASK;
-            backoff.snooze();
+impl<T> Drop for UpdateLock<'_, T> {
+    fn drop(&mut self) {
+        if self.mutating {
+            // the readers are drained, nobody is credited
+            self.link.unlock_update(self.ptr, NoCredit);
+        } else {
+            self.link.unlock();
         }
+    }
+}
 
-        core::sync::atomic::fence(Ordering::Acquire);
+/// The error of the `try_` methods that would have to wait otherwise
+#[cfg(feature = "panic-free")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WouldBlock;
 
-        let addr = (old & !REFCOUNT_MASK) >> LEADING_BITS;
-        Ptr { addr }.ptr()
+#[cfg(feature = "panic-free")]
+impl fmt::Display for WouldBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the operation would block")
     }
 }
 
-impl<T: fmt::Debug> fmt::Debug for LinkWrapper<T> {
+impl<T> fmt::Debug for LinkWrapper<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let ptr = self.get_ref();
-        f.debug_struct("Link").field("ptr", &ptr).finish()
+        let state = self.state();
+        f.debug_struct("Link")
+            .field("ptr", &core::ptr::without_provenance::<T>(state.addr))
+            .field("readers", &state.readers)
+            .field("updating", &state.updating)
+            .field("mutating", &state.mutating)
+            .field("generation", &state.generation)
+            .finish()
     }
 }
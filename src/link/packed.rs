@@ -0,0 +1,512 @@
+use core::marker::PhantomData;
+use core::sync::atomic::Ordering;
+
+use super::{CellState, Credit, Stats, Validation, ValueId, Writers};
+use crate::backoff::Backoff;
+use crate::park;
+use crate::sync::{const_fn, fence, AtomicPtr, AtomicU64};
+
+const LEADING_BITS: usize = 8;
+const ALIGN_BITS: usize = 3;
+
+const LOWER_MASK: usize = (1 << ALIGN_BITS) - 1;
+// the low bits of the pointer are shifted into the flags
+pub(crate) const PTR_ALIGN: usize = 1 << ALIGN_BITS;
+const HIGHER_MASK: usize = !((1 << (usize::MAX.leading_ones() as usize - LEADING_BITS)) - 1);
+const REFCOUNT_MASK: usize = (1 << (LEADING_BITS + ALIGN_BITS)) - 1;
+const UPDTATE_MASK: usize = 1 << (LEADING_BITS + ALIGN_BITS - 1);
+// set by an updater that mutates the data in place, readers must wait
+const MUTATE_MASK: usize = 1 << (LEADING_BITS + ALIGN_BITS - 2);
+const UPDATE_REF_MASK: usize = REFCOUNT_MASK & !UPDTATE_MASK & !MUTATE_MASK;
+
+// the packed word keeps the provenance of the pointer, only the address
+// is shifted to make room for the reader count and the flags
+#[inline]
+fn pack<T>(ptr: *const T) -> *mut T {
+    debug_assert!(ptr.addr() & LOWER_MASK == 0);
+    debug_assert!(
+        ptr.addr() & HIGHER_MASK == 0,
+        "the high pointer bits are in use, enable the `two-word` feature"
+    );
+    ptr.cast_mut().map_addr(|addr| addr << LEADING_BITS)
+}
+
+#[inline]
+fn unpack<T>(word: *mut T) -> *const T {
+    word.map_addr(|addr| (addr & !REFCOUNT_MASK) >> LEADING_BITS)
+}
+
+/// A wrapper of the pointer to the inner Arc data
+pub(crate) struct LinkWrapper<T> {
+    ptr: AtomicPtr<T>,
+    // generation of the last publication, only bumped with update flag held
+    gen: AtomicU64,
+    writers: Writers,
+    stats: Stats,
+    phantom: PhantomData<*const T>,
+}
+
+impl<T> LinkWrapper<T> {
+    const_fn! {
+        #[inline]
+        pub(crate) const fn none() -> Self {
+            LinkWrapper {
+                ptr: AtomicPtr::new(core::ptr::null_mut()),
+                gen: AtomicU64::new(0),
+                writers: Writers::new(),
+                stats: Stats::new(),
+                phantom: PhantomData,
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn new(ptr: *const T) -> Self {
+        LinkWrapper {
+            ptr: AtomicPtr::new(pack(ptr)),
+            gen: AtomicU64::new(0),
+            writers: Writers::new(),
+            stats: Stats::new(),
+            phantom: PhantomData,
+        }
+    }
+
+    // the number of in-flight readers of the swapped out `word`, they are
+    // credited to the old pointer's own refcount, see `publish`
+    #[inline]
+    fn laggards(ptr: *const T, word: *mut T) -> usize {
+        // a null pointer has no refcount to credit
+        if ptr.is_null() {
+            0
+        } else {
+            word.addr() & UPDATE_REF_MASK
+        }
+    }
+
+    // Writers never wait for the in-flight readers. The readers count is
+    // swapped out together with the old pointer, and the writer credits the
+    // counts of those laggard readers to the old pointer's refcount with
+    // `credit` before the swap is published. The laggards find the pointer
+    // changed in `dec_ref` and release the credited refcount instead, which
+    // is always there by then. Every swap returns the old pointer and the
+    // new generation.
+    //
+    // The publication is always at least `AcqRel`, `success` only makes it
+    // `SeqCst`.
+    pub(crate) unsafe fn compare_exchange(
+        &self,
+        current: *const T,
+        new: *const T,
+        success: Ordering,
+        failure: Ordering,
+        credit: impl Credit<T>,
+    ) -> Result<(*const T, u64), *const T> {
+        let old = current.addr() << LEADING_BITS;
+
+        self.writers.lock(&self.stats);
+        let mut cur = self.ptr.load(failure);
+        loop {
+            if cur.addr() & !REFCOUNT_MASK != old {
+                self.writers.unlock();
+                return Err(unpack(cur));
+            }
+            // wait for the other updater
+            if cur.addr() & (UPDTATE_MASK | MUTATE_MASK) != 0 {
+                self.wait_unlocked();
+                cur = self.ptr.load(failure);
+                continue;
+            }
+            // take the update flag, the pointer can't change under it
+            match self.ptr.compare_exchange_weak(
+                cur,
+                cur.map_addr(|addr| addr | UPDTATE_MASK),
+                Ordering::Acquire,
+                failure,
+            ) {
+                Ok(_) => return Ok(self.publish(new, success, credit)),
+                Err(word) => {
+                    self.stats.cas_failed();
+                    cur = word;
+                }
+            }
+        }
+    }
+
+    // like `compare_exchange`, but give up instead of retrying on a failed
+    // CAS or a held update flag, so the failure may be spurious
+    pub(crate) unsafe fn compare_exchange_weak(
+        &self,
+        current: *const T,
+        new: *const T,
+        success: Ordering,
+        failure: Ordering,
+        credit: impl Credit<T>,
+    ) -> Result<(*const T, u64), *const T> {
+        let old = current.addr() << LEADING_BITS;
+
+        self.writers.lock(&self.stats);
+        let cur = self.ptr.load(failure);
+        if cur.addr() & !REFCOUNT_MASK != old || cur.addr() & (UPDTATE_MASK | MUTATE_MASK) != 0 {
+            self.writers.unlock();
+            return Err(unpack(cur));
+        }
+        match self.ptr.compare_exchange_weak(
+            cur,
+            cur.map_addr(|addr| addr | UPDTATE_MASK),
+            Ordering::Acquire,
+            failure,
+        ) {
+            Ok(_) => Ok(self.publish(new, success, credit)),
+            Err(word) => {
+                self.stats.cas_failed();
+                self.writers.unlock();
+                Err(unpack(word))
+            }
+        }
+    }
+
+    // publish the new pointer, see `compare_exchange` for the return value
+    #[inline]
+    pub(crate) fn update(&self, ptr: *const T, credit: impl Credit<T>) -> (*const T, u64) {
+        self.lock_read();
+        self.publish(ptr, Ordering::AcqRel, credit)
+    }
+
+    // the writers never wait for the readers, so it always publishes,
+    // see `RcuCell::set_bounded`
+    #[inline]
+    pub(crate) fn update_bounded(
+        &self,
+        ptr: *const T,
+        credit: impl Credit<T>,
+    ) -> Option<(*const T, u64)> {
+        Some(self.update(ptr, credit))
+    }
+
+    // publish the new pointer only if no other writer holds the update flag,
+    // see `compare_exchange` for the return value
+    #[cfg(feature = "panic-free")]
+    pub(crate) fn try_update(
+        &self,
+        ptr: *const T,
+        credit: impl Credit<T>,
+    ) -> Option<(*const T, u64)> {
+        use Ordering::*;
+        if !self.writers.try_lock() {
+            return None;
+        }
+
+        let mut old = self.ptr.load(Relaxed);
+        loop {
+            if old.addr() & (UPDTATE_MASK | MUTATE_MASK) != 0 {
+                self.writers.unlock();
+                return None;
+            }
+            match self.ptr.compare_exchange_weak(
+                old,
+                old.map_addr(|addr| addr | UPDTATE_MASK),
+                Acquire,
+                Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(addr) => {
+                    self.stats.cas_failed();
+                    old = addr;
+                }
+            }
+        }
+        // the mutate flag is only set by the update flag holder, there are
+        // no readers to drain and no backoff to panic in
+        Some(self.swap_in(ptr, AcqRel, credit, || {}))
+    }
+
+    // this is only used after lock_read,
+    // see `compare_exchange` for the return value
+    #[inline]
+    pub(crate) fn unlock_update(&self, ptr: *const T, credit: impl Credit<T>) -> (*const T, u64) {
+        self.publish(ptr, Ordering::AcqRel, credit)
+    }
+
+    // Swap in the new pointer with the update flag held, so the old pointer
+    // can't change under us, only its reader count. The laggard readers in
+    // the swapped word are credited before the CAS, and the credit is fixed
+    // up if the CAS fails on a changed count. A withdrawn credit never
+    // drops the last count, the old pointer is still owned by the link.
+    fn publish(&self, ptr: *const T, success: Ordering, credit: impl Credit<T>) -> (*const T, u64) {
+        let backoff = Backoff::new();
+        self.swap_in(ptr, success, credit, || {
+            self.stats.spin();
+            backoff.snooze();
+        })
+    }
+
+    // the loop of `publish`, `drain` waits for the readers to leave an
+    // in-place mutation
+    #[inline]
+    fn swap_in(
+        &self,
+        ptr: *const T,
+        success: Ordering,
+        credit: impl Credit<T>,
+        drain: impl Fn(),
+    ) -> (*const T, u64) {
+        use Ordering::*;
+        let new = pack(ptr).map_addr(|addr| addr | UPDTATE_MASK);
+        let success = if success == SeqCst { SeqCst } else { AcqRel };
+
+        let mut old = self.ptr.load(Relaxed);
+        let old_ptr = unpack(old);
+        let mut credited = 0;
+        loop {
+            // the readers are drained during an in-place mutation, they
+            // are never credited
+            if old.addr() & MUTATE_MASK != 0 && old.addr() & UPDATE_REF_MASK != 0 {
+                drain();
+                old = self.ptr.load(Relaxed);
+                continue;
+            }
+            let laggards = Self::laggards(old_ptr, old);
+            if laggards != credited {
+                unsafe { credit.credit(old_ptr, laggards as isize - credited as isize) };
+                credited = laggards;
+            }
+            match self.ptr.compare_exchange_weak(old, new, success, Relaxed) {
+                Ok(_) => break,
+                Err(addr) => {
+                    self.stats.cas_failed();
+                    old = addr;
+                }
+            }
+        }
+
+        (old_ptr, self.unlock_gen())
+    }
+
+    // release the update flag without publishing anything,
+    // this is only used after lock_read
+    #[inline]
+    pub(crate) fn unlock(&self) {
+        self.ptr.fetch_and(!UPDTATE_MASK, Ordering::Release);
+        park::wake(self.key());
+        self.writers.unlock();
+    }
+
+    // wait for the update flag and the mutate flag to be released
+    #[inline]
+    fn wait_unlocked(&self) {
+        self.stats.lock_wait();
+        park::wait_until(self.key(), || {
+            self.ptr.load(Ordering::Relaxed).addr() & (UPDTATE_MASK | MUTATE_MASK) == 0
+        });
+    }
+
+    // the parking key of the writers waiting for the update flag,
+    // and the key of the tasks waiting for the next publication
+    #[inline]
+    pub(crate) fn key(&self) -> usize {
+        (self as *const Self).addr()
+    }
+
+    // bump the generation of a publication that still holds the update
+    // flag, then release the flag for other writers
+    #[inline]
+    fn unlock_gen(&self) -> u64 {
+        let gen = self.gen.fetch_add(1, Ordering::Release) + 1;
+        self.ptr.fetch_and(!UPDTATE_MASK, Ordering::Release);
+        park::wake(self.key());
+        self.writers.unlock();
+        #[cfg(feature = "async")]
+        crate::notify::wake(self.key());
+        gen
+    }
+
+    #[inline]
+    pub(crate) fn is_none(&self) -> bool {
+        self.ptr.load(Ordering::Relaxed).addr() & !REFCOUNT_MASK == 0
+    }
+
+    // Readers never overflow the count into the flags, if the count is
+    // saturated they spin until some readers release, the same for the
+    // data that is being mutated in place.
+    #[inline]
+    pub(crate) fn inc_ref(&self) -> *const T {
+        if let Some(ptr) = self.try_inc_ref() {
+            return ptr;
+        }
+        let backoff = Backoff::new();
+        loop {
+            self.stats.spin();
+            backoff.snooze();
+            if let Some(ptr) = self.try_inc_ref() {
+                return ptr;
+            }
+        }
+    }
+
+    // like `inc_ref`, but never wait, give up if the data is being
+    // mutated in place or the reader count is saturated
+    #[inline]
+    pub(crate) fn try_inc_ref(&self) -> Option<*const T> {
+        let mut cur = self.ptr.load(Ordering::Relaxed);
+        loop {
+            let refs = cur.addr() & UPDATE_REF_MASK;
+            if cur.addr() & MUTATE_MASK != 0 || refs == UPDATE_REF_MASK {
+                return None;
+            }
+            match self.ptr.compare_exchange_weak(
+                cur,
+                cur.wrapping_byte_add(1),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Some(unpack(cur)),
+                Err(word) => {
+                    self.stats.cas_failed();
+                    cur = word;
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get_ref(&self) -> *const T {
+        unpack(self.ptr.load(Ordering::Acquire))
+    }
+
+    // read the inner pointer through exclusive access, no atomic needed
+    #[inline]
+    pub(crate) fn get_mut(&mut self) -> *const T {
+        #[cfg(not(loom))]
+        let word = *self.ptr.get_mut();
+        #[cfg(loom)]
+        let word = self.ptr.with_mut(|word| *word);
+        unpack(word)
+    }
+
+    // release the count taken by `inc_ref` that returned `ptr`.
+    // Return true if a writer has swapped out the pointer while we are in
+    // flight and credited our count to the pointer's own refcount, then
+    // the caller must release that refcount.
+    //
+    // If the same pointer is published again meanwhile, we may release a
+    // count of a newer reader instead, which is harmless, the newer reader
+    // would then release our credited refcount of the same pointer.
+    #[inline]
+    pub(crate) fn dec_ref(&self, ptr: *const T) -> bool {
+        use Ordering::*;
+        let addr = ptr.addr() << LEADING_BITS;
+        let mut cur = self.ptr.load(Relaxed);
+        loop {
+            if cur.addr() & !REFCOUNT_MASK != addr || cur.addr() & UPDATE_REF_MASK == 0 {
+                // the credit is done before the swap is published, see
+                // `publish`, a null pointer is never credited
+                fence(Acquire);
+                return !ptr.is_null();
+            }
+            match self
+                .ptr
+                .compare_exchange_weak(cur, cur.wrapping_byte_sub(1), Release, Relaxed)
+            {
+                Ok(_) => return false,
+                Err(addr) => {
+                    self.stats.cas_failed();
+                    cur = addr;
+                }
+            }
+        }
+    }
+
+    // read the inner Arc and increase the ref count
+    // to prevet other writer to update the inner Arc
+    // should be paired used with unlock_update
+    #[inline]
+    pub(crate) fn lock_read(&self) -> *const T {
+        use Ordering::*;
+
+        self.writers.lock(&self.stats);
+        let word = self.ptr.load(Relaxed);
+        let mut old = word.map_addr(|addr| addr & !UPDTATE_MASK); // clear the update flag
+        let mut new = word.map_addr(|addr| addr | UPDTATE_MASK); // set the update flag
+
+        while let Err(word) = self.ptr.compare_exchange_weak(old, new, Release, Relaxed) {
+            self.stats.cas_failed();
+            if word.addr() & UPDTATE_MASK != 0 {
+                self.wait_unlocked();
+            }
+            let word = self.ptr.load(Relaxed);
+            old = word.map_addr(|addr| addr & !UPDTATE_MASK);
+            new = word.map_addr(|addr| addr | UPDTATE_MASK);
+        }
+
+        fence(Ordering::Acquire);
+
+        unpack(old)
+    }
+
+    // block new readers and wait all the in-flight readers release,
+    // this is only used after lock_read and should be paired used with
+    // unlock_update which would clear the mutate flag
+    pub(crate) fn lock_mut(&self) {
+        use Ordering::*;
+        self.ptr.fetch_or(MUTATE_MASK, Relaxed);
+        let backoff = Backoff::new();
+        while self.ptr.load(Relaxed).addr() & UPDATE_REF_MASK != 0 {
+            self.stats.spin();
+            backoff.snooze();
+        }
+        fence(Acquire);
+    }
+
+    // the contention counters of the link
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub(crate) fn stats(&self) -> super::ContentionStats {
+        self.stats.snapshot()
+    }
+
+    // the generation of the latest publication
+    #[inline]
+    pub(crate) fn gen(&self) -> u64 {
+        self.gen.load(Ordering::Acquire)
+    }
+
+    // the identity of the current value, the generation is bumped before
+    // the update flag is released, so retry until a consistent pair is read
+    pub(crate) fn identity(&self) -> ValueId {
+        let backoff = Backoff::new();
+        loop {
+            let gen = self.gen.load(Ordering::Acquire);
+            let word = self.ptr.load(Ordering::Acquire).addr();
+            if word & UPDTATE_MASK == 0 && self.gen.load(Ordering::Relaxed) == gen {
+                return ValueId::new((word & !REFCOUNT_MASK) >> LEADING_BITS, gen);
+            }
+            self.stats.spin();
+            backoff.snooze();
+        }
+    }
+
+    // decode the packed word
+    pub(crate) fn state(&self) -> CellState {
+        let word = self.ptr.load(Ordering::Acquire).addr();
+        CellState {
+            addr: (word & !REFCOUNT_MASK) >> LEADING_BITS,
+            readers: word & UPDATE_REF_MASK,
+            updating: word & UPDTATE_MASK != 0,
+            mutating: word & MUTATE_MASK != 0,
+            generation: self.gen(),
+        }
+    }
+
+    // decode the packed word and check its invariants
+    pub(crate) fn validate(&self) -> Validation {
+        let state = self.state();
+        let align = core::mem::align_of::<T>().max(1 << ALIGN_BITS);
+        Validation {
+            addr: state.addr,
+            readers: state.readers,
+            update_locked: state.updating,
+            misaligned: state.addr & (align - 1) != 0,
+            readers_overflow: state.readers == UPDATE_REF_MASK,
+        }
+    }
+}
@@ -0,0 +1,404 @@
+use core::marker::PhantomData;
+use core::sync::atomic::Ordering;
+
+use super::{CellState, Credit, Stats, Validation, ValueId, Writers};
+use crate::backoff::Backoff;
+use crate::park;
+use crate::sync::{const_fn, fence, AtomicPtr, AtomicUsize};
+
+const UPDTATE_MASK: usize = 1 << (usize::BITS - 1);
+// set by an updater that is about to swap or mutate the data,
+// new readers must wait
+const BLOCK_MASK: usize = 1 << (usize::BITS - 2);
+const REFCOUNT_MASK: usize = !(UPDTATE_MASK | BLOCK_MASK);
+// the pointer is kept whole in its own word
+pub(crate) const PTR_ALIGN: usize = 1;
+
+/// A wrapper of the pointer to the inner Arc data
+///
+/// There are no spare bits in the pointer for the reader count, so the
+/// pointer and the reader count are kept in two words. The readers can't
+/// be credited to the old pointer like the packed layout does, instead
+/// a writer blocks new readers and waits for the in-flight ones before
+/// swapping the pointer, a read only holds the count to clone the Arc.
+pub(crate) struct LinkWrapper<T> {
+    ptr: AtomicPtr<T>,
+    // the update flag, the block flag and the reader count
+    state: AtomicUsize,
+    // generation of the last publication, only bumped with update flag held
+    gen: AtomicUsize,
+    writers: Writers,
+    stats: Stats,
+    phantom: PhantomData<*const T>,
+}
+
+impl<T> LinkWrapper<T> {
+    const_fn! {
+        #[inline]
+        pub(crate) const fn none() -> Self {
+            Self::new(core::ptr::null())
+        }
+    }
+
+    const_fn! {
+        #[inline]
+        pub(crate) const fn new(ptr: *const T) -> Self {
+            LinkWrapper {
+                ptr: AtomicPtr::new(ptr.cast_mut()),
+                state: AtomicUsize::new(0),
+                gen: AtomicUsize::new(0),
+                writers: Writers::new(),
+                stats: Stats::new(),
+                phantom: PhantomData,
+            }
+        }
+    }
+
+    // take the update flag for the writer
+    #[inline]
+    fn lock_update(&self) {
+        let mut old = self.state.load(Ordering::Relaxed) & !UPDTATE_MASK;
+        while let Err(state) = self.state.compare_exchange_weak(
+            old,
+            old | UPDTATE_MASK,
+            Ordering::Acquire,
+            Ordering::Relaxed,
+        ) {
+            self.stats.cas_failed();
+            if state & UPDTATE_MASK != 0 {
+                self.stats.lock_wait();
+                park::wait_until(self.key(), || {
+                    self.state.load(Ordering::Relaxed) & UPDTATE_MASK == 0
+                });
+            }
+            old = self.state.load(Ordering::Relaxed) & !UPDTATE_MASK;
+        }
+    }
+
+    // the parking key of the writers waiting for the update flag,
+    // and the key of the tasks waiting for the next publication
+    #[inline]
+    pub(crate) fn key(&self) -> usize {
+        (self as *const Self).addr()
+    }
+
+    // swap the pointer with the update flag held, block the new readers
+    // and wait for the in-flight readers before that
+    #[inline]
+    fn publish(&self, ptr: *const T) -> (*const T, u64) {
+        self.lock_mut();
+        let old = self.ptr.swap(ptr.cast_mut(), Ordering::AcqRel);
+        (old, self.unlock_gen())
+    }
+
+    // Writers wait for the in-flight readers, so there is never a laggard
+    // reader to `credit`, every swap returns the old pointer and the new
+    // generation.
+    pub(crate) unsafe fn compare_exchange(
+        &self,
+        current: *const T,
+        new: *const T,
+        success: Ordering,
+        failure: Ordering,
+        credit: impl Credit<T>,
+    ) -> Result<(*const T, u64), *const T> {
+        let _ = (success, credit);
+        self.writers.lock(&self.stats);
+        self.lock_update();
+        let cur = self.ptr.load(failure);
+        if !core::ptr::eq(cur, current) {
+            self.unlock();
+            return Err(cur);
+        }
+        Ok(self.publish(new))
+    }
+
+    // like `compare_exchange`, but give up instead of waiting for a held
+    // update flag or retrying a failed CAS, so the failure may be spurious
+    pub(crate) unsafe fn compare_exchange_weak(
+        &self,
+        current: *const T,
+        new: *const T,
+        success: Ordering,
+        failure: Ordering,
+        credit: impl Credit<T>,
+    ) -> Result<(*const T, u64), *const T> {
+        let _ = (success, credit);
+        self.writers.lock(&self.stats);
+        let state = self.state.load(Ordering::Relaxed) & !UPDTATE_MASK;
+        if self
+            .state
+            .compare_exchange_weak(
+                state,
+                state | UPDTATE_MASK,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            self.stats.cas_failed();
+            self.writers.unlock();
+            return Err(self.ptr.load(failure));
+        }
+        let cur = self.ptr.load(failure);
+        if !core::ptr::eq(cur, current) {
+            self.unlock();
+            return Err(cur);
+        }
+        Ok(self.publish(new))
+    }
+
+    // publish the new pointer, see `compare_exchange` for the return value
+    pub(crate) fn update(&self, ptr: *const T, credit: impl Credit<T>) -> (*const T, u64) {
+        let _ = credit;
+        self.writers.lock(&self.stats);
+        self.lock_update();
+        self.publish(ptr)
+    }
+
+    // like `update`, but give up if the in-flight readers are not drained
+    // within a backoff, see `RcuCell::set_bounded`
+    pub(crate) fn update_bounded(
+        &self,
+        ptr: *const T,
+        credit: impl Credit<T>,
+    ) -> Option<(*const T, u64)> {
+        let _ = credit;
+        self.writers.lock(&self.stats);
+        self.lock_update();
+        if !self.try_lock_mut() {
+            self.unlock();
+            return None;
+        }
+        let old = self.ptr.swap(ptr.cast_mut(), Ordering::AcqRel);
+        Some((old, self.unlock_gen()))
+    }
+
+    // publish the new pointer only if no other writer holds the update flag
+    // and no reader is in flight, see `compare_exchange` for the return value
+    #[cfg(feature = "panic-free")]
+    pub(crate) fn try_update(
+        &self,
+        ptr: *const T,
+        credit: impl Credit<T>,
+    ) -> Option<(*const T, u64)> {
+        let _ = credit;
+        if !self.writers.try_lock() {
+            return None;
+        }
+        if self
+            .state
+            .compare_exchange(
+                0,
+                UPDTATE_MASK | BLOCK_MASK,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            self.writers.unlock();
+            return None;
+        }
+        let old = self.ptr.swap(ptr.cast_mut(), Ordering::AcqRel);
+        Some((old, self.unlock_gen()))
+    }
+
+    // this is only used after lock_read,
+    // see `compare_exchange` for the return value
+    #[inline]
+    pub(crate) fn unlock_update(&self, ptr: *const T, credit: impl Credit<T>) -> (*const T, u64) {
+        let _ = credit;
+        self.publish(ptr)
+    }
+
+    // release the update flag without publishing anything,
+    // this is only used after lock_read
+    #[inline]
+    pub(crate) fn unlock(&self) {
+        self.state.fetch_and(!UPDTATE_MASK, Ordering::Release);
+        park::wake(self.key());
+        self.writers.unlock();
+    }
+
+    // bump the generation of a publication that still holds the update
+    // flag, then release the flags for other writers and the readers
+    #[inline]
+    fn unlock_gen(&self) -> u64 {
+        let gen = self.gen.fetch_add(1, Ordering::Release) as u64 + 1;
+        self.state
+            .fetch_and(!(UPDTATE_MASK | BLOCK_MASK), Ordering::Release);
+        park::wake(self.key());
+        self.writers.unlock();
+        #[cfg(feature = "async")]
+        crate::notify::wake(self.key());
+        gen
+    }
+
+    #[inline]
+    pub(crate) fn is_none(&self) -> bool {
+        self.ptr.load(Ordering::Relaxed).is_null()
+    }
+
+    // Readers never overflow the count into the flags, if the count is
+    // saturated they spin until some readers release, the same for a
+    // writer that is waiting for the in-flight readers.
+    #[inline]
+    pub(crate) fn inc_ref(&self) -> *const T {
+        if let Some(ptr) = self.try_inc_ref() {
+            return ptr;
+        }
+        let backoff = Backoff::new();
+        loop {
+            self.stats.spin();
+            backoff.snooze();
+            if let Some(ptr) = self.try_inc_ref() {
+                return ptr;
+            }
+        }
+    }
+
+    // like `inc_ref`, but never wait, give up if a writer is waiting for
+    // the in-flight readers or the reader count is saturated
+    #[inline]
+    pub(crate) fn try_inc_ref(&self) -> Option<*const T> {
+        let mut cur = self.state.load(Ordering::Relaxed);
+        loop {
+            if cur & BLOCK_MASK != 0 || cur & REFCOUNT_MASK == REFCOUNT_MASK {
+                return None;
+            }
+            match self.state.compare_exchange_weak(
+                cur,
+                cur + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                // the writer can't swap the pointer until we release the count
+                Ok(_) => return Some(self.ptr.load(Ordering::Acquire)),
+                Err(state) => {
+                    self.stats.cas_failed();
+                    cur = state;
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get_ref(&self) -> *const T {
+        self.ptr.load(Ordering::Acquire)
+    }
+
+    // read the inner pointer through exclusive access, no atomic needed
+    #[inline]
+    pub(crate) fn get_mut(&mut self) -> *const T {
+        #[cfg(not(loom))]
+        let ptr = *self.ptr.get_mut();
+        #[cfg(loom)]
+        let ptr = self.ptr.with_mut(|ptr| *ptr);
+        ptr
+    }
+
+    // release the count taken by `inc_ref` that returned `ptr`,
+    // the pointer is never swapped out under a reader, so never credited
+    #[inline]
+    pub(crate) fn dec_ref(&self, ptr: *const T) -> bool {
+        let _ = ptr;
+        self.state.fetch_sub(1, Ordering::Release);
+        false
+    }
+
+    // read the inner Arc and take the update flag
+    // to prevet other writer to update the inner Arc
+    // should be paired used with unlock_update
+    #[inline]
+    pub(crate) fn lock_read(&self) -> *const T {
+        self.writers.lock(&self.stats);
+        self.lock_update();
+        self.ptr.load(Ordering::Acquire)
+    }
+
+    // block new readers and wait all the in-flight readers release,
+    // this is only used after lock_read and should be paired used with
+    // unlock_update which would clear the block flag
+    pub(crate) fn lock_mut(&self) {
+        use Ordering::*;
+        self.state.fetch_or(BLOCK_MASK, Relaxed);
+        let backoff = Backoff::new();
+        while self.state.load(Relaxed) & REFCOUNT_MASK != 0 {
+            self.stats.spin();
+            backoff.snooze();
+        }
+        fence(Acquire);
+    }
+
+    // like `lock_mut`, but unblock the readers and give up if they are not
+    // drained when the backoff completes
+    fn try_lock_mut(&self) -> bool {
+        use Ordering::*;
+        self.state.fetch_or(BLOCK_MASK, Relaxed);
+        let backoff = Backoff::new();
+        while self.state.load(Relaxed) & REFCOUNT_MASK != 0 {
+            if backoff.is_completed() {
+                self.state.fetch_and(!BLOCK_MASK, Relaxed);
+                return false;
+            }
+            self.stats.spin();
+            backoff.snooze();
+        }
+        fence(Acquire);
+        true
+    }
+
+    // the contention counters of the link
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub(crate) fn stats(&self) -> super::ContentionStats {
+        self.stats.snapshot()
+    }
+
+    // the generation of the latest publication
+    #[inline]
+    pub(crate) fn gen(&self) -> u64 {
+        self.gen.load(Ordering::Acquire) as u64
+    }
+
+    // the identity of the current value, the generation is bumped before
+    // the update flag is released, so retry until a consistent pair is read
+    pub(crate) fn identity(&self) -> ValueId {
+        let backoff = Backoff::new();
+        loop {
+            let gen = self.gen.load(Ordering::Acquire);
+            let ptr = self.ptr.load(Ordering::Acquire);
+            let state = self.state.load(Ordering::Acquire);
+            if state & UPDTATE_MASK == 0 && self.gen.load(Ordering::Relaxed) == gen {
+                return ValueId::new(ptr.addr(), gen as u64);
+            }
+            self.stats.spin();
+            backoff.snooze();
+        }
+    }
+
+    // decode the two words
+    pub(crate) fn state(&self) -> CellState {
+        let state = self.state.load(Ordering::Acquire);
+        CellState {
+            addr: self.ptr.load(Ordering::Acquire).addr(),
+            readers: state & REFCOUNT_MASK,
+            updating: state & UPDTATE_MASK != 0,
+            mutating: state & BLOCK_MASK != 0,
+            generation: self.gen(),
+        }
+    }
+
+    // decode the two words and check their invariants
+    pub(crate) fn validate(&self) -> Validation {
+        let state = self.state();
+        let align = core::mem::align_of::<T>();
+        Validation {
+            addr: state.addr,
+            readers: state.readers,
+            update_locked: state.updating,
+            misaligned: state.addr & (align - 1) != 0,
+            readers_overflow: state.readers == REFCOUNT_MASK,
+        }
+    }
+}
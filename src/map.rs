@@ -0,0 +1,238 @@
+//! A concurrent hash map with lock-free readers, built on `RcuCell`s.
+//!
+//! Every bucket is an `RcuCell` of the entries in it, a reader clones the
+//! value out of the bucket without any lock, and a writer publishes a copy
+//! of the bucket with the change, only writers of the same bucket wait for
+//! each other. The copy only clones the `Arc`s of the entries, neither the
+//! keys nor the values.
+//!
+//! The number of buckets is fixed when the map is created, so size it with
+//! `with_capacity` if many entries are expected.
+//!
+//! ```
+//! use rcu_cell::map::RcuHashMap;
+//! use std::collections::hash_map::RandomState;
+//!
+//! // `RcuHashMap::new()` with the `std` feature
+//! let map = RcuHashMap::with_hasher(RandomState::new());
+//! assert!(map.insert("a", 1).is_none());
+//! assert_eq!(map.get("a").as_deref(), Some(&1));
+//! assert_eq!(map.insert("a", 2).as_deref(), Some(&1));
+//! assert_eq!(map.remove("a").as_deref(), Some(&2));
+//! assert!(map.is_empty());
+//! ```
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::hash::{BuildHasher, Hash};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::RcuCell;
+
+const DEFAULT_BUCKETS: usize = 64;
+
+type Bucket<K, V> = Vec<(Arc<K>, Arc<V>)>;
+
+/// A concurrent hash map whose buckets are rcu cells
+pub struct RcuHashMap<K, V, S = DefaultHashBuilder> {
+    buckets: Vec<RcuCell<Bucket<K, V>>>,
+    len: AtomicUsize,
+    hash_builder: S,
+}
+
+/// The default hasher of `RcuHashMap`
+#[cfg(feature = "std")]
+pub type DefaultHashBuilder = std::collections::hash_map::RandomState;
+/// There is no default hasher without the `std` feature,
+/// create the map with `RcuHashMap::with_hasher` instead
+#[cfg(not(feature = "std"))]
+pub type DefaultHashBuilder = ();
+
+impl<K, V, S> core::fmt::Debug for RcuHashMap<K, V, S>
+where
+    K: core::fmt::Debug,
+    V: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V> Default for RcuHashMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V> RcuHashMap<K, V> {
+    /// create an empty map with the default number of buckets
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    /// create an empty map with at least `capacity` buckets
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> RcuHashMap<K, V, S> {
+    /// create an empty map with the default number of buckets
+    /// that uses `hash_builder` to hash the keys
+    #[inline]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_capacity_and_hasher(DEFAULT_BUCKETS, hash_builder)
+    }
+
+    /// create an empty map with at least `capacity` buckets
+    /// that uses `hash_builder` to hash the keys
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let n = capacity.max(1).next_power_of_two();
+        RcuHashMap {
+            buckets: (0..n).map(|_| RcuCell::none()).collect(),
+            len: AtomicUsize::new(0),
+            hash_builder,
+        }
+    }
+
+    /// the number of entries, it's racy with the concurrent writers
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// check if there is no entry, it's racy with the concurrent writers
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// remove all the entries
+    pub fn clear(&self) {
+        for bucket in &self.buckets {
+            if let Some(old) = bucket.take() {
+                self.len.fetch_sub(old.len(), Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Iterate over the entries, every bucket is a consistent snapshot,
+    /// but the writes to the buckets not visited yet are observed.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            buckets: self.buckets.iter(),
+            bucket: None,
+            pos: 0,
+        }
+    }
+}
+
+impl<K, V, S> RcuHashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    #[inline]
+    fn bucket<Q>(&self, key: &Q) -> &RcuCell<Bucket<K, V>>
+    where
+        Q: Hash + ?Sized,
+    {
+        let hash = self.hash_builder.hash_one(key) as usize;
+        &self.buckets[hash & (self.buckets.len() - 1)]
+    }
+
+    /// get the value of the key
+    pub fn get<Q>(&self, key: &Q) -> Option<Arc<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let bucket = self.bucket(key).read()?;
+        bucket
+            .iter()
+            .find(|(k, _)| (**k).borrow() == key)
+            .map(|(_, v)| v.clone())
+    }
+
+    /// check if the key is in the map
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// insert the value of the key and return the old one
+    pub fn insert(&self, key: K, value: impl Into<Arc<V>>) -> Option<Arc<V>> {
+        let value = value.into();
+        let mut old = None;
+        self.bucket(&key).update(|bucket| {
+            let mut new = bucket.as_deref().cloned().unwrap_or_default();
+            match new.iter_mut().find(|(k, _)| **k == key) {
+                Some((_, v)) => old = Some(core::mem::replace(v, value)),
+                None => {
+                    // count the entry before it's published, so a racing
+                    // `clear` never takes it out of the count first
+                    self.len.fetch_add(1, Ordering::Relaxed);
+                    new.push((Arc::new(key), value));
+                }
+            }
+            Some(new)
+        });
+        old
+    }
+
+    /// remove the key and return its value
+    pub fn remove<Q>(&self, key: &Q) -> Option<Arc<V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut old = None;
+        // nothing is published if the key is absent
+        let _ = self.bucket(key).update_if::<(), _>(|bucket| {
+            let bucket = bucket.ok_or(())?;
+            let i = bucket
+                .iter()
+                .position(|(k, _)| (**k).borrow() == key)
+                .ok_or(())?;
+            let mut new = Vec::clone(bucket);
+            old = Some(new.swap_remove(i).1);
+            Ok((!new.is_empty()).then_some(Arc::new(new)))
+        });
+        if old.is_some() {
+            self.len.fetch_sub(1, Ordering::Relaxed);
+        }
+        old
+    }
+}
+
+/// The iterator of `RcuHashMap::iter`
+pub struct Iter<'a, K, V> {
+    buckets: core::slice::Iter<'a, RcuCell<Bucket<K, V>>>,
+    bucket: Option<Arc<Bucket<K, V>>>,
+    pos: usize,
+}
+
+impl<K, V> Iterator for Iter<'_, K, V> {
+    type Item = (Arc<K>, Arc<V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.bucket.as_ref().and_then(|b| b.get(self.pos)) {
+                self.pos += 1;
+                return Some(entry.clone());
+            }
+            self.bucket = self.buckets.next()?.read();
+            self.pos = 0;
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use alloc::sync::Arc;
+
+use crate::{RcuCell, ValueId};
+
+/// RCU cell that always holds a value, it behaves like `RwLock<Arc<T>>`
+///
+/// It's built from an initial value and there is no way to take the value
+/// out, so the reads return the `Arc` without an `Option`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RcuCellNonEmpty<T> {
+    cell: RcuCell<T>,
+}
+
+impl<T: Default> Default for RcuCellNonEmpty<T> {
+    fn default() -> Self {
+        RcuCellNonEmpty::new(T::default())
+    }
+}
+
+impl<T> From<Arc<T>> for RcuCellNonEmpty<T> {
+    fn from(data: Arc<T>) -> Self {
+        RcuCellNonEmpty {
+            cell: RcuCell::from(data),
+        }
+    }
+}
+
+impl<T> RcuCellNonEmpty<T> {
+    /// create the rcu cell from the initial value
+    #[inline]
+    pub fn new(data: T) -> Self {
+        RcuCellNonEmpty {
+            cell: RcuCell::some(data),
+        }
+    }
+
+    /// convert the rcu cell to the inner Arc value
+    #[inline]
+    pub fn into_arc(self) -> Arc<T> {
+        self.cell.into_arc().expect("rcu cell is never empty")
+    }
+
+    /// convert the rcu cell to the inner value, the value is cloned
+    /// if the inner Arc is still shared by others
+    #[inline]
+    pub fn into_inner(self) -> T
+    where
+        T: Clone,
+    {
+        Arc::unwrap_or_clone(self.into_arc())
+    }
+
+    /// get a mutable reference to the inner value through exclusive access,
+    /// return `None` if the inner Arc is shared
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.cell.get_mut()
+    }
+
+    /// read out the inner Arc value
+    #[inline]
+    pub fn read(&self) -> Arc<T> {
+        self.cell.read().expect("rcu cell is never empty")
+    }
+
+    /// write a value to the rcu cell and return the old value
+    #[inline]
+    pub fn write(&self, data: impl Into<Arc<T>>) -> Arc<T> {
+        self.cell.write(data).expect("rcu cell is never empty")
+    }
+
+    /// Atomicly update the value with a closure and return the old value,
+    /// see `RcuCell::update`
+    pub fn update<R, F>(&self, f: F) -> Arc<T>
+    where
+        F: FnOnce(Arc<T>) -> R,
+        R: Into<Arc<T>>,
+    {
+        let old = self.cell.update(|v| v.map(f));
+        old.expect("rcu cell is never empty")
+    }
+
+    /// Atomicly mutate the value with `Arc::make_mut` semantics,
+    /// see `RcuCell::update_mut`
+    #[inline]
+    pub fn update_mut<F>(&self, f: F)
+    where
+        T: Clone,
+        F: FnOnce(&mut T),
+    {
+        self.cell.update_mut(f);
+    }
+
+    /// the version of the rcu cell, see `RcuCell::version`
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.cell.version()
+    }
+
+    /// the identity of the current value, see `RcuCell::identity`
+    #[inline]
+    pub fn identity(&self) -> ValueId {
+        self.cell.identity()
+    }
+
+    /// check if the rcu cell holds the same inner Arc
+    #[inline]
+    pub fn arc_eq(&self, data: &Arc<T>) -> bool {
+        self.cell.arc_eq(data)
+    }
+
+    /// check if two rcu cells point to the same inner Arc
+    #[inline]
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        RcuCell::ptr_eq(&this.cell, &other.cell)
+    }
+}
@@ -0,0 +1,373 @@
+// Waking the tasks that wait for the next publications of a cell.
+//
+// Like the parked writers, the wakers are kept in a small global table
+// hashed by the address of the cell, so a cell doesn't pay for them, and
+// a publication only takes the lock of a slot if some task is waiting.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+use futures_core::Stream;
+use std::sync::{Mutex, PoisonError};
+
+use crate::RcuCell;
+
+/// The future returned by `RcuCell::changed`
+///
+/// It resolves once a value is published to the rcu cell after the
+/// future is created, no matter if it's polled before that.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Changed<'a, T> {
+    cell: &'a RcuCell<T>,
+    version: u64,
+    waiter: Waiter,
+}
+
+impl<'a, T> Changed<'a, T> {
+    #[inline]
+    pub(crate) fn new(cell: &'a RcuCell<T>) -> Self {
+        Changed {
+            cell,
+            version: cell.version(),
+            waiter: Waiter::new(cell.key()),
+        }
+    }
+
+    #[inline]
+    fn is_changed(&self) -> bool {
+        self.cell.version() != self.version
+    }
+}
+
+impl<T> Future for Changed<'_, T> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        if this.is_changed() {
+            return Poll::Ready(());
+        }
+        this.waiter.register(cx.waker());
+        if this.is_changed() {
+            return Poll::Ready(());
+        }
+        Poll::Pending
+    }
+}
+
+impl<T> core::fmt::Debug for Changed<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Changed")
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+/// The stream returned by `RcuCell::subscribe`
+///
+/// It yields the current value first, then every value published after
+/// the last yielded one. The values published between two polls are
+/// coalesced, only the latest one is yielded. It never ends.
+#[must_use = "streams do nothing unless polled"]
+pub struct Subscription<'a, T> {
+    cell: &'a RcuCell<T>,
+    // the version of the last yielded value
+    version: Option<u64>,
+    waiter: Waiter,
+}
+
+impl<'a, T> Subscription<'a, T> {
+    #[inline]
+    pub(crate) fn new(cell: &'a RcuCell<T>) -> Self {
+        Subscription {
+            cell,
+            version: None,
+            waiter: Waiter::new(cell.key()),
+        }
+    }
+
+    // read the latest value if it's not yielded yet, the version is loaded
+    // again after reading, so a value is never yielded twice
+    fn next_value(&mut self) -> Option<Option<Arc<T>>> {
+        let mut version = self.cell.version();
+        loop {
+            if self.version == Some(version) {
+                return None;
+            }
+            let value = self.cell.read();
+            let latest = self.cell.version();
+            if latest == version {
+                self.version = Some(version);
+                return Some(value);
+            }
+            version = latest;
+        }
+    }
+}
+
+impl<T> Stream for Subscription<'_, T> {
+    type Item = Option<Arc<T>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(value) = this.next_value() {
+            return Poll::Ready(Some(value));
+        }
+        this.waiter.register(cx.waker());
+        match this.next_value() {
+            Some(value) => Poll::Ready(Some(value)),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<T> core::fmt::Debug for Subscription<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Subscription")
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+/// The stream returned by `RcuCell::subscribe_filtered`
+///
+/// It yields the new value of every transition that `pred(old, new)`
+/// accepts, where `old` is the last value the stream observed, yielded or
+/// not. The value when it's created is the first `old` and is not yielded.
+/// Like `Subscription`, the values published between two polls are
+/// coalesced, so a transition is only seen between the observed values.
+/// The task is still polled on the publications, but the consumer is only
+/// resumed by the accepted ones. It never ends.
+#[must_use = "streams do nothing unless polled"]
+pub struct FilteredSubscription<'a, T, F> {
+    inner: Subscription<'a, T>,
+    // the last observed value, the old side of the next transition
+    last: Option<Arc<T>>,
+    pred: F,
+}
+
+impl<'a, T, F> FilteredSubscription<'a, T, F>
+where
+    F: FnMut(Option<&T>, Option<&T>) -> bool,
+{
+    #[inline]
+    pub(crate) fn new(cell: &'a RcuCell<T>, pred: F) -> Self {
+        let mut inner = Subscription::new(cell);
+        // the first read of a subscription always returns a value
+        let last = inner.next_value().unwrap_or_default();
+        FilteredSubscription { inner, last, pred }
+    }
+}
+
+// the predicate is never pinned
+impl<T, F> Unpin for FilteredSubscription<'_, T, F> {}
+
+impl<T, F> Stream for FilteredSubscription<'_, T, F>
+where
+    F: FnMut(Option<&T>, Option<&T>) -> bool,
+{
+    type Item = Option<Arc<T>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let value = match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(value)) => value,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+            let old = core::mem::replace(&mut this.last, value.clone());
+            if (this.pred)(old.as_deref(), value.as_deref()) {
+                return Poll::Ready(Some(value));
+            }
+        }
+    }
+}
+
+impl<T, F> core::fmt::Debug for FilteredSubscription<'_, T, F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("FilteredSubscription")
+            .field("version", &self.inner.version)
+            .finish()
+    }
+}
+
+/// The stream returned by `RcuCell::watch_map`
+///
+/// It yields the projection of the current value first, then the
+/// projection of every newly observed value that is not equal to the last
+/// yielded one. Like `Subscription`, the values published between two
+/// polls are coalesced. It never ends.
+#[must_use = "streams do nothing unless polled"]
+pub struct WatchMap<'a, T, U, F> {
+    inner: Subscription<'a, T>,
+    // the last yielded projection
+    last: Option<U>,
+    map: F,
+}
+
+impl<'a, T, U, F> WatchMap<'a, T, U, F> {
+    #[inline]
+    pub(crate) fn new(cell: &'a RcuCell<T>, map: F) -> Self {
+        WatchMap {
+            inner: Subscription::new(cell),
+            last: None,
+            map,
+        }
+    }
+}
+
+// neither the projection nor the map is ever pinned
+impl<T, U, F> Unpin for WatchMap<'_, T, U, F> {}
+
+impl<T, U, F> Stream for WatchMap<'_, T, U, F>
+where
+    F: FnMut(Option<&T>) -> U,
+    U: PartialEq + Clone,
+{
+    type Item = U;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<U>> {
+        let this = self.get_mut();
+        loop {
+            let value = match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(value)) => value,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+            let projected = (this.map)(value.as_deref());
+            if this.last.as_ref() != Some(&projected) {
+                this.last = Some(projected.clone());
+                return Poll::Ready(Some(projected));
+            }
+        }
+    }
+}
+
+impl<T, U: core::fmt::Debug, F> core::fmt::Debug for WatchMap<'_, T, U, F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("WatchMap")
+            .field("version", &self.inner.version)
+            .field("last", &self.last)
+            .finish()
+    }
+}
+
+// wake up the tasks waiting on `key`, called after every publication
+#[inline]
+pub(crate) fn wake(key: usize) {
+    let slot = slot(key);
+    fence(Ordering::SeqCst);
+    if slot.waiters.load(Ordering::Relaxed) != 0 {
+        notify(slot, key);
+    }
+}
+
+struct Entry {
+    key: usize,
+    id: usize,
+    waker: Waker,
+}
+
+struct Slot {
+    waiters: AtomicUsize,
+    entries: Mutex<Vec<Entry>>,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Slot {
+            waiters: AtomicUsize::new(0),
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, Vec<Entry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+// A task waiting on `key`, the registration is removed when it's woken up
+// or dropped. Check the condition again after registering, either the
+// check sees the publication or the publisher sees the waker.
+pub(crate) struct Waiter {
+    key: usize,
+    // the id of the registered waker
+    id: Option<usize>,
+}
+
+impl Waiter {
+    #[inline]
+    pub(crate) fn new(key: usize) -> Self {
+        Waiter { key, id: None }
+    }
+
+    pub(crate) fn register(&mut self, waker: &Waker) {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let slot = slot(self.key);
+        let mut entries = slot.entries();
+        if let Some(id) = self.id {
+            if let Some(entry) = entries.iter_mut().find(|e| e.id == id) {
+                entry.waker.clone_from(waker);
+                return;
+            }
+        }
+        let id = *self
+            .id
+            .get_or_insert_with(|| NEXT_ID.fetch_add(1, Ordering::Relaxed));
+        entries.push(Entry {
+            key: self.key,
+            id,
+            waker: waker.clone(),
+        });
+        slot.waiters.fetch_add(1, Ordering::Relaxed);
+        drop(entries);
+        // pairs with the fence in `wake`
+        fence(Ordering::SeqCst);
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        let Some(id) = self.id else { return };
+        let slot = slot(self.key);
+        let mut entries = slot.entries();
+        if let Some(i) = entries.iter().position(|e| e.id == id) {
+            entries.swap_remove(i);
+            slot.waiters.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// The wakers are called after the lock is released, a waker may drop the
+// task and so the `Changed` future that would take the lock again.
+//
+// This never unwinds into the publisher, it would abort instead, so the
+// `try_` methods that publish stay panic free.
+#[cold]
+extern "C" fn notify(slot: &Slot, key: usize) {
+    let mut woken = Vec::new();
+    let mut entries = slot.entries();
+    let mut i = 0;
+    while i < entries.len() {
+        if entries[i].key == key {
+            woken.push(entries.swap_remove(i).waker);
+        } else {
+            i += 1;
+        }
+    }
+    slot.waiters.fetch_sub(woken.len(), Ordering::Relaxed);
+    drop(entries);
+    woken.into_iter().for_each(Waker::wake);
+}
+
+static SLOTS: [Slot; 64] = [const { Slot::new() }; 64];
+
+#[inline]
+fn slot(key: usize) -> &'static Slot {
+    // the cells are at least 8 bytes apart
+    &SLOTS[(key >> 3 ^ key >> 9) % SLOTS.len()]
+}
@@ -0,0 +1,113 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::RcuCell;
+
+type BoxedObserver<T> = Box<dyn Fn(Option<&T>, Option<&T>) + Send + Sync>;
+
+/// RCU cell that calls its observers after every publication, built by
+/// `RcuCell::with_observer`
+///
+/// The observers are called with the old and the new value by the writer
+/// that published them, after the publication, so they never block the
+/// readers or the other writers. Concurrent writers call them concurrently,
+/// and not necessarily in the publication order.
+///
+/// The writes are only available through this type, so none of them can
+/// bypass the observers.
+pub struct ObservedRcuCell<T> {
+    cell: RcuCell<T>,
+    observers: Vec<BoxedObserver<T>>,
+}
+
+impl<T> core::fmt::Debug for ObservedRcuCell<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("ObservedRcuCell")
+            .field("cell", &self.cell.debug_state())
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
+
+impl<T> ObservedRcuCell<T> {
+    #[inline]
+    pub(crate) fn new(cell: RcuCell<T>) -> Self {
+        ObservedRcuCell {
+            cell,
+            observers: Vec::new(),
+        }
+    }
+
+    /// add another observer, the observers are called in the order they
+    /// are added
+    pub fn with_observer<F>(mut self, f: F) -> Self
+    where
+        F: Fn(Option<&T>, Option<&T>) + Send + Sync + 'static,
+    {
+        self.observers.push(Box::new(f));
+        self
+    }
+
+    /// remove the observers and return the inner rcu cell
+    #[inline]
+    pub fn into_inner(self) -> RcuCell<T> {
+        self.cell
+    }
+
+    #[inline]
+    fn notify(&self, old: &Option<Arc<T>>, new: &Option<Arc<T>>) {
+        for f in &self.observers {
+            f(old.as_deref(), new.as_deref());
+        }
+    }
+
+    /// check if the rcu cell is empty
+    #[inline]
+    pub fn is_none(&self) -> bool {
+        self.cell.is_none()
+    }
+
+    /// read out the inner Arc value
+    #[inline]
+    pub fn read(&self) -> Option<Arc<T>> {
+        self.cell.read()
+    }
+
+    /// the version of the rcu cell, see `RcuCell::version`
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.cell.version()
+    }
+
+    /// write an option arc value to the rcu cell and return the old value
+    pub fn set(&self, data: Option<Arc<T>>) -> Option<Arc<T>> {
+        let old = self.cell.set(data.clone());
+        self.notify(&old, &data);
+        old
+    }
+
+    /// take the value from the rcu cell, leave the rcu cell empty
+    #[inline]
+    pub fn take(&self) -> Option<Arc<T>> {
+        self.set(None)
+    }
+
+    /// write a value to the rcu cell and return the old value
+    #[inline]
+    pub fn write(&self, data: impl Into<Arc<T>>) -> Option<Arc<T>> {
+        self.set(Some(data.into()))
+    }
+
+    /// Atomicly update the value with a closure and return the old value,
+    /// see `RcuCell::update`
+    pub fn update<R, F>(&self, f: F) -> Option<Arc<T>>
+    where
+        F: FnOnce(Option<Arc<T>>) -> Option<R>,
+        R: Into<Arc<T>>,
+    {
+        let (old, new) = self.cell.update_and_fetch(f);
+        self.notify(&old, &new);
+        old
+    }
+}
@@ -0,0 +1,90 @@
+use alloc::sync::Arc;
+use core::ops::Deref;
+
+use crate::RcuCell;
+
+/// The common operations of the rcu cells of every backend
+///
+/// Code that is generic over it works with any of the cells, the `Arc`
+/// based `RcuCell` and the `hazard::RcuCell` with the `hazard` feature.
+/// The returned old values differ between the backends, so the writes
+/// don't return them, use the methods of the cell to get them.
+pub trait RcuCellOps<T> {
+    /// the guard of a read value, the value is valid while it's alive
+    type Guard<'a>: Deref<Target = T>
+    where
+        Self: 'a;
+
+    /// read the value
+    fn read(&self) -> Option<Self::Guard<'_>>;
+
+    /// write a value to the rcu cell
+    fn write(&self, data: T);
+
+    /// take the value out of the rcu cell, leave the rcu cell empty
+    fn take(&self);
+
+    /// Atomicly update the value with a closure that is called with the
+    /// current value, the writers are serialized while it runs.
+    fn update<F>(&self, f: F)
+    where
+        F: FnOnce(Option<&T>) -> Option<T>;
+}
+
+impl<T> RcuCellOps<T> for RcuCell<T> {
+    type Guard<'a>
+        = Arc<T>
+    where
+        T: 'a;
+
+    #[inline]
+    fn read(&self) -> Option<Arc<T>> {
+        RcuCell::read(self)
+    }
+
+    #[inline]
+    fn write(&self, data: T) {
+        RcuCell::write(self, data);
+    }
+
+    #[inline]
+    fn take(&self) {
+        RcuCell::take(self);
+    }
+
+    #[inline]
+    fn update<F>(&self, f: F)
+    where
+        F: FnOnce(Option<&T>) -> Option<T>,
+    {
+        RcuCell::update(self, |old| f(old.as_deref()));
+    }
+}
+
+#[cfg(feature = "hazard")]
+impl<T: Send + 'static> RcuCellOps<T> for crate::hazard::RcuCell<T> {
+    type Guard<'a> = crate::hazard::Guard<'a, T>;
+
+    #[inline]
+    fn read(&self) -> Option<Self::Guard<'_>> {
+        crate::hazard::RcuCell::read(self)
+    }
+
+    #[inline]
+    fn write(&self, data: T) {
+        crate::hazard::RcuCell::write(self, data);
+    }
+
+    #[inline]
+    fn take(&self) {
+        crate::hazard::RcuCell::take(self);
+    }
+
+    #[inline]
+    fn update<F>(&self, f: F)
+    where
+        F: FnOnce(Option<&T>) -> Option<T>,
+    {
+        crate::hazard::RcuCell::update(self, f);
+    }
+}
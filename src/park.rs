@@ -0,0 +1,145 @@
+// Waiting for the update lock of a cell.
+//
+// A writer spins for a while on a held update lock, then with the `std`
+// feature it's parked on a small global table of condition variables
+// hashed by the address of the lock, until the lock holder wakes it up.
+// Without the `std` feature it just keeps spinning, and so does a writer
+// running in a coroutine, which yields to the scheduler instead.
+
+#[cfg(feature = "std")]
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::sync::{Condvar, Mutex, PoisonError};
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+#[cfg(feature = "std")]
+use crate::backoff::in_coroutine;
+use crate::backoff::Backoff;
+
+// wait until `ready` returns true, `key` is the address of the lock
+#[inline]
+pub(crate) fn wait_until<F: Fn() -> bool>(key: usize, ready: F) {
+    let backoff = Backoff::new();
+    while !ready() {
+        #[cfg(feature = "std")]
+        if backoff.is_completed() && !in_coroutine() {
+            slot(key).park(&ready, None);
+            return;
+        }
+        #[cfg(not(feature = "std"))]
+        let _ = key;
+        backoff.snooze();
+    }
+}
+
+// like `wait_until`, but give up at the `deadline`,
+// return whether `ready` returned true
+#[cfg(feature = "std")]
+pub(crate) fn wait_until_deadline<F: Fn() -> bool>(
+    key: usize,
+    ready: F,
+    deadline: Instant,
+) -> bool {
+    let backoff = Backoff::new();
+    while !ready() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        if backoff.is_completed() && !in_coroutine() {
+            return slot(key).park(&ready, Some(deadline));
+        }
+        backoff.snooze();
+    }
+    true
+}
+
+// wake up the threads parked on `key`, called after the lock is released
+#[inline]
+pub(crate) fn wake(key: usize) {
+    #[cfg(feature = "std")]
+    slot(key).unpark();
+    #[cfg(not(feature = "std"))]
+    let _ = key;
+}
+
+#[cfg(feature = "std")]
+struct Slot {
+    waiters: AtomicUsize,
+    lock: Mutex<()>,
+    cond: Condvar,
+}
+
+#[cfg(feature = "std")]
+impl Slot {
+    const fn new() -> Self {
+        Slot {
+            waiters: AtomicUsize::new(0),
+            lock: Mutex::new(()),
+            cond: Condvar::new(),
+        }
+    }
+
+    // park until `ready` returns true or the `deadline` is reached,
+    // return whether `ready` returned true
+    #[cold]
+    fn park<F: Fn() -> bool>(&self, ready: &F, deadline: Option<Instant>) -> bool {
+        self.waiters.fetch_add(1, Ordering::Relaxed);
+        // pairs with the fence in `unpark`, either we see the lock released
+        // or the waker sees us waiting
+        fence(Ordering::SeqCst);
+        let mut guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut is_ready = ready();
+        while !is_ready {
+            guard = match deadline {
+                None => self
+                    .cond
+                    .wait(guard)
+                    .unwrap_or_else(PoisonError::into_inner),
+                Some(deadline) => {
+                    let Some(timeout) = deadline.checked_duration_since(Instant::now()) else {
+                        break;
+                    };
+                    self.cond
+                        .wait_timeout(guard, timeout)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+            };
+            is_ready = ready();
+        }
+        drop(guard);
+        self.waiters.fetch_sub(1, Ordering::Relaxed);
+        is_ready
+    }
+
+    #[inline]
+    fn unpark(&self) {
+        fence(Ordering::SeqCst);
+        if self.waiters.load(Ordering::Relaxed) != 0 {
+            notify(self);
+        }
+    }
+}
+
+// The waiter checks `ready` with the mutex held, so taking it here makes
+// sure it's either not checked yet or already waiting.
+//
+// This never unwinds into the lock holder, it would abort instead, so the
+// `try_` methods that release the lock stay panic free.
+#[cfg(feature = "std")]
+#[cold]
+extern "C" fn notify(slot: &Slot) {
+    drop(slot.lock.lock().unwrap_or_else(PoisonError::into_inner));
+    slot.cond.notify_all();
+}
+
+#[cfg(feature = "std")]
+static SLOTS: [Slot; 64] = [const { Slot::new() }; 64];
+
+#[cfg(feature = "std")]
+#[inline]
+fn slot(key: usize) -> &'static Slot {
+    // the locks are at least 8 bytes apart
+    &SLOTS[(key >> 3 ^ key >> 9) % SLOTS.len()]
+}
@@ -0,0 +1,105 @@
+use alloc::sync::Arc;
+
+use crate::{RcuBox, RcuCell};
+
+struct Node<T> {
+    // taken by the pop that turns the node into the sentinel
+    value: RcuBox<T>,
+    next: RcuCell<Node<T>>,
+}
+
+impl<T> Drop for Node<T> {
+    // unlink the nodes one by one, a recursive drop of a long
+    // queue would overflow the thread stack
+    fn drop(&mut self) {
+        let mut next = self.next.take();
+        while let Some(node) = next {
+            next = match Arc::try_unwrap(node) {
+                Ok(node) => node.next.take(),
+                Err(_) => break,
+            };
+        }
+    }
+}
+
+/// An unbounded multi-producer multi-consumer FIFO queue of linked rcu
+/// cells
+///
+/// The head is a sentinel node whose next node holds the first value.
+/// `push` links a new node after the tail under the update lock of the tail
+/// cell, so the pushes are linked in order and a returned push is always
+/// visible to `pop`. `pop` moves the head to the next node under the update
+/// lock of the head cell and takes its value, that node becomes the new
+/// sentinel. The producers and the consumers never wait for each other,
+/// and a popped node is reclaimed by reference counting, so there is no
+/// ABA problem.
+pub struct RcuQueue<T> {
+    head: RcuCell<Node<T>>,
+    tail: RcuCell<Node<T>>,
+}
+
+impl<T> core::fmt::Debug for RcuQueue<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("RcuQueue")
+            .field("head", &self.head.debug_state())
+            .field("tail", &self.tail.debug_state())
+            .finish()
+    }
+}
+
+impl<T> Default for RcuQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> RcuQueue<T> {
+    /// create an empty queue
+    pub fn new() -> Self {
+        let sentinel = Arc::new(Node {
+            value: RcuBox::none(),
+            next: RcuCell::none(),
+        });
+        RcuQueue {
+            head: RcuCell::from(sentinel.clone()),
+            tail: RcuCell::from(sentinel),
+        }
+    }
+
+    /// check if the queue is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head
+            .with_ref(|head| head.expect("the queue always has a head").next.is_none())
+    }
+
+    /// push a value to the back of the queue
+    pub fn push(&self, value: T) {
+        let node = Arc::new(Node {
+            value: RcuBox::some(value),
+            next: RcuCell::none(),
+        });
+        self.tail.update(|tail| {
+            let tail = tail.expect("the queue always has a tail");
+            tail.next.write(node.clone());
+            Some(node)
+        });
+    }
+
+    /// pop the value at the front of the queue
+    pub fn pop(&self) -> Option<T> {
+        let mut value = None;
+        self.head.update(|head| {
+            let head = head.expect("the queue always has a head");
+            match head.next.read() {
+                Some(next) => {
+                    value = next.value.take();
+                    Some(next)
+                }
+                // keep the sentinel
+                None => Some(head),
+            }
+        });
+        value.map(|value| *value)
+    }
+}
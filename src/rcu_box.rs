@@ -0,0 +1,175 @@
+use alloc::boxed::Box;
+use core::fmt;
+use core::mem::{align_of, ManuallyDrop};
+use core::ptr;
+use core::sync::atomic::Ordering;
+
+use crate::link::{LinkWrapper, NoCredit};
+use crate::sync::{const_fn, fence};
+
+// the packed link needs 8-byte aligned pointers
+#[repr(C, align(8))]
+struct Slot<T>(T);
+
+#[inline]
+fn into_slot<T>(data: Option<Box<T>>) -> *const Slot<T> {
+    match data {
+        // the layouts are the same, the allocation is reused
+        Some(data) if align_of::<T>() >= align_of::<Slot<T>>() => Box::into_raw(data) as *const _,
+        Some(data) => Box::into_raw(Box::new(Slot(*data))),
+        None => ptr::null(),
+    }
+}
+
+#[inline]
+fn from_slot<T>(ptr: *const Slot<T>) -> Option<Box<T>> {
+    if ptr.is_null() {
+        return None;
+    }
+    let slot = unsafe { Box::from_raw(ptr as *mut Slot<T>) };
+    if align_of::<T>() >= align_of::<Slot<T>>() {
+        Some(unsafe { Box::from_raw(Box::into_raw(slot) as *mut T) })
+    } else {
+        Some(Box::new(slot.0))
+    }
+}
+
+// release the reader count even if the reader panics, or the writers
+// would wait for it forever
+struct ReadGuard<'a, T>(&'a LinkWrapper<Slot<T>>, *const Slot<T>);
+
+impl<T> Drop for ReadGuard<'_, T> {
+    fn drop(&mut self) {
+        // the value is never swapped out under a reader, so never credited
+        let credited = self.0.dec_ref(self.1);
+        debug_assert!(!credited);
+        fence(Ordering::Acquire);
+    }
+}
+
+/// RCU cell that owns its value exclusively, it behaves like
+/// `RwLock<Option<Box<T>>>`
+///
+/// The readers borrow the value under the reader count with `read_with`
+/// and never clone it, so the value is a plain `Box` without the reference
+/// counts of an `Arc`. A writer blocks the new readers and waits for the
+/// in-flight ones to leave before it swaps the value out, then the old
+/// value is returned as a `Box` that nobody else can reach.
+pub struct RcuBox<T> {
+    link: LinkWrapper<Slot<T>>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for RcuBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.read_with(|v| f.debug_struct("RcuBox").field("value", &v).finish())
+    }
+}
+
+unsafe impl<T: Send> Send for RcuBox<T> {}
+unsafe impl<T: Send + Sync> Sync for RcuBox<T> {}
+
+impl<T> Drop for RcuBox<T> {
+    fn drop(&mut self) {
+        let _ = from_slot(self.link.get_mut());
+    }
+}
+
+impl<T> Default for RcuBox<T> {
+    fn default() -> Self {
+        RcuBox::none()
+    }
+}
+
+impl<T> From<Box<T>> for RcuBox<T> {
+    fn from(data: Box<T>) -> Self {
+        RcuBox {
+            link: LinkWrapper::new(into_slot(Some(data))),
+        }
+    }
+}
+
+impl<T> RcuBox<T> {
+    const_fn! {
+        /// create an empty rcu box
+        #[inline]
+        pub const fn none() -> Self {
+            RcuBox {
+                link: LinkWrapper::none(),
+            }
+        }
+    }
+
+    /// create rcu box from a value
+    #[inline]
+    pub fn some(data: T) -> Self {
+        RcuBox {
+            link: LinkWrapper::new(Box::into_raw(Box::new(Slot(data)))),
+        }
+    }
+
+    /// create rcu box from value that can be converted to Option<T>
+    #[inline]
+    pub fn new(data: impl Into<Option<T>>) -> Self {
+        match data.into() {
+            Some(data) => Self::some(data),
+            None => Self::none(),
+        }
+    }
+
+    /// convert the rcu box to the inner value
+    #[inline]
+    pub fn into_inner(self) -> Option<Box<T>> {
+        let this = ManuallyDrop::new(self);
+        from_slot(this.link.get_ref())
+    }
+
+    /// get a mutable reference to the inner value through exclusive access
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        let ptr = self.link.get_mut() as *mut Slot<T>;
+        unsafe { ptr.as_mut() }.map(|slot| &mut slot.0)
+    }
+
+    /// check if the rcu box is empty
+    #[inline]
+    pub fn is_none(&self) -> bool {
+        self.link.is_none()
+    }
+
+    /// Call `f` with a reference to the value, the writers wait for it to
+    /// return, so it should be as short as possible.
+    pub fn read_with<R>(&self, f: impl FnOnce(Option<&T>) -> R) -> R {
+        let ptr = self.link.inc_ref();
+        let _guard = ReadGuard(&self.link, ptr);
+        f(unsafe { ptr.as_ref() }.map(|slot| &slot.0))
+    }
+
+    /// write an option boxed value to the rcu box and return the old value,
+    /// it waits for the in-flight readers of the old value
+    pub fn set(&self, data: Option<Box<T>>) -> Option<Box<T>> {
+        let new_ptr = into_slot(data);
+        let old_ptr = self.link.lock_read();
+        self.link.lock_mut();
+        // the readers are drained, nobody is credited
+        self.link.unlock_update(new_ptr, NoCredit);
+        from_slot(old_ptr)
+    }
+
+    /// write a boxed value to the rcu box and return the old value
+    #[inline]
+    pub fn swap(&self, data: impl Into<Box<T>>) -> Option<Box<T>> {
+        self.set(Some(data.into()))
+    }
+
+    /// take the value from the rcu box, leave the rcu box empty
+    #[inline]
+    pub fn take(&self) -> Option<Box<T>> {
+        self.set(None)
+    }
+
+    /// the version of the rcu box, see `RcuCell::version`
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.link.gen()
+    }
+}
@@ -1,22 +1,86 @@
-use alloc::sync::Arc;
+use alloc::sync::{Arc, Weak};
+use core::fmt;
+use core::hash::{Hash, Hasher};
 use core::mem::ManuallyDrop;
 use core::ptr;
 use core::sync::atomic::Ordering;
 
-use crate::link::LinkWrapper;
-use crate::ArcPointer;
+use crate::field::RcuOptionField;
+#[cfg(feature = "stats")]
+use crate::link::ContentionStats;
+#[cfg(feature = "panic-free")]
+use crate::link::WouldBlock;
+use crate::link::{CellState, Credit, LinkWrapper, UpdateLock, Validation, ValueId};
+#[cfg(feature = "async")]
+use crate::notify::{Changed, FilteredSubscription, Subscription, WatchMap};
+#[cfg(feature = "std")]
+use crate::park;
+use crate::sync::{const_fn, fence};
+#[cfg(feature = "std")]
+use crate::ReapedRcuCell;
+use crate::{ArcPointer, ObservedRcuCell, RcuWeak};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
 
 #[inline]
 fn ptr_to_arc<T>(ptr: *const T) -> Option<Arc<T>> {
     unsafe { ArcPointer::from_raw(ptr) }
 }
 
+// credit the counts of the laggard readers to the swapped out Arc,
+// see `Credit`
+struct ArcCredit;
+
+impl<T> Credit<T> for ArcCredit {
+    #[inline]
+    unsafe fn credit(&self, ptr: *const T, n: isize) {
+        for _ in 0..n {
+            Arc::increment_strong_count(ptr);
+        }
+        for _ in n..0 {
+            Arc::decrement_strong_count(ptr);
+        }
+    }
+}
+
+// release the reader count of `ptr`, if a writer has credited the count
+// to the Arc, release that strong count instead
+#[inline]
+fn dec_ref<T>(link: &LinkWrapper<T>, ptr: *const T) {
+    if link.dec_ref(ptr) {
+        unsafe { Arc::decrement_strong_count(ptr) };
+    }
+}
+
+// release the reader count even if the reader panics, or the writers
+// would wait for it forever, or the credited count would be leaked
+struct ReadGuard<'a, T>(&'a LinkWrapper<T>, *const T);
+
+impl<T> Drop for ReadGuard<'_, T> {
+    fn drop(&mut self) {
+        dec_ref(self.0, self.1);
+        fence(Ordering::Acquire);
+    }
+}
+
 /// RCU cell, it behaves like `RwLock<Option<Arc<T>>>`
-#[derive(Debug)]
 pub struct RcuCell<T> {
     link: LinkWrapper<T>,
 }
 
+// show the stored value, but never wait for it, the value may be mutated
+// in place by the thread that is formatting it
+impl<T: fmt::Debug> fmt::Debug for RcuCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut d = f.debug_struct("RcuCell");
+        match self.read_nowait() {
+            Some(value) => d.field("value", &value),
+            None => d.field("value", &format_args!("<locked>")),
+        };
+        d.field("generation", &self.version()).finish()
+    }
+}
+
 unsafe impl<T: Send> Send for RcuCell<T> {}
 unsafe impl<T: Send + Sync> Sync for RcuCell<T> {}
 
@@ -51,12 +115,24 @@ impl<T> From<Option<Arc<T>>> for RcuCell<T> {
     }
 }
 
+/// Compare the stored values, two cells that point to the same inner Arc
+/// are equal without comparing the values.
+impl<T: PartialEq> PartialEq for RcuCell<T> {
+    fn eq(&self, other: &Self) -> bool {
+        RcuCell::ptr_eq(self, other) || self.read() == other.read()
+    }
+}
+
+impl<T: Eq> Eq for RcuCell<T> {}
+
 impl<T> RcuCell<T> {
-    /// create an empty rcu cell instance
-    #[inline]
-    pub const fn none() -> Self {
-        RcuCell {
-            link: LinkWrapper::new(ptr::null()),
+    const_fn! {
+        /// create an empty rcu cell instance
+        #[inline]
+        pub const fn none() -> Self {
+            RcuCell {
+                link: LinkWrapper::none(),
+            }
         }
     }
 
@@ -88,6 +164,51 @@ impl<T> RcuCell<T> {
         ret
     }
 
+    /// convert the rcu cell to the inner value, the value is cloned
+    /// if the inner Arc is still shared by others
+    #[inline]
+    pub fn into_inner(self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.into_arc().map(Arc::unwrap_or_clone)
+    }
+
+    /// turn the rcu cell into one that calls `f` with the old and the new
+    /// value after every `set`/`write`/`update`, see `ObservedRcuCell`
+    #[inline]
+    pub fn with_observer<F>(self, f: F) -> ObservedRcuCell<T>
+    where
+        F: Fn(Option<&T>, Option<&T>) + Send + Sync + 'static,
+    {
+        ObservedRcuCell::new(self).with_observer(f)
+    }
+
+    /// turn the rcu cell into one that drops the replaced values on a
+    /// background thread, see `ReapedRcuCell`
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn with_background_drop(self) -> ReapedRcuCell<T>
+    where
+        T: Send + 'static,
+    {
+        ReapedRcuCell::new(self)
+    }
+
+    /// get a mutable reference to the inner value through exclusive access,
+    /// return `None` if the cell is empty or the inner Arc is shared
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        let ptr = self.link.get_mut();
+        if ptr.is_null() {
+            return None;
+        }
+        let mut arc = ManuallyDrop::new(unsafe { Arc::from_raw(ptr) });
+        // the cell is exclusively borrowed, nobody else can clone the Arc
+        Arc::get_mut(&mut arc)?;
+        Some(unsafe { &mut *(ptr as *mut T) })
+    }
+
     /// check if the rcu cell is empty
     #[inline]
     pub fn is_none(&self) -> bool {
@@ -97,8 +218,42 @@ impl<T> RcuCell<T> {
     /// write an option arc value to the rcu cell and return the old value
     #[inline]
     pub fn set(&self, data: Option<Arc<T>>) -> Option<Arc<T>> {
+        self.set_gen(data).0
+    }
+
+    /// write an option arc value to the rcu cell and return the old value
+    /// together with the generation assigned to this publication.
+    /// Generations of the same cell are strictly increasing in the order the
+    /// values are published, no matter how many writers are racing.
+    #[inline]
+    pub fn set_gen(&self, data: Option<Arc<T>>) -> (Option<Arc<T>>, u64) {
         let new_ptr = data.into_raw();
-        ptr_to_arc(self.link.update(new_ptr))
+        let (old_ptr, gen) = self.link.update(new_ptr, ArcCredit);
+        (ptr_to_arc(old_ptr), gen)
+    }
+
+    /// Store an option arc value to the rcu cell like `set` and drop the old
+    /// value, with the memory ordering of the store against the surrounding
+    /// accesses, like `AtomicPtr::store`.
+    ///
+    /// The value is always published with release semantics, so that the
+    /// readers can safely use it, `Relaxed` and `Release` are the same as
+    /// `set`, `SeqCst` adds a sequentially consistent fence after it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Acquire` or `AcqRel`.
+    #[inline]
+    pub fn store(&self, data: Option<Arc<T>>, order: Ordering) {
+        match order {
+            Ordering::Acquire => panic!("there is no such thing as an acquire store"),
+            Ordering::AcqRel => panic!("there is no such thing as an acquire-release store"),
+            _ => {}
+        }
+        drop(self.set(data));
+        if order == Ordering::SeqCst {
+            fence(Ordering::SeqCst);
+        }
     }
 
     /// take the value from the rcu cell, leave the rcu cell empty
@@ -114,24 +269,268 @@ impl<T> RcuCell<T> {
         self.set(Some(data))
     }
 
+    /// Write an option arc value to the rcu cell like `set`, but never wait
+    /// for the in-flight readers without a bound, so a stalled reader can't
+    /// block the writer (e.g. a low priority reader that is preempted in the
+    /// middle of a read). It still waits for the other writers.
+    ///
+    /// On the packed layout it always succeeds like every write, each
+    /// laggard reader is credited a strong count of the old Arc, and the old
+    /// value is dropped by whoever releases the last count, which may be one
+    /// of the laggard readers instead of this writer. On the two-word layout
+    /// the writer blocks the new readers and waits for the in-flight ones
+    /// for a short backoff, then gives up and gives back `data`.
+    ///
+    /// Return the old value on success, otherwise give back `data`.
+    #[inline]
+    pub fn set_bounded(&self, data: Option<Arc<T>>) -> Result<Option<Arc<T>>, Option<Arc<T>>> {
+        let new_ptr = data.into_raw();
+        match self.link.update_bounded(new_ptr, ArcCredit) {
+            Some((old_ptr, _gen)) => Ok(ptr_to_arc(old_ptr)),
+            None => Err(ptr_to_arc(new_ptr)),
+        }
+    }
+
+    /// write a value to the rcu cell with bounded wait, see `set_bounded`
+    #[inline]
+    pub fn write_bounded(&self, data: impl Into<Arc<T>>) -> Result<Option<Arc<T>>, Arc<T>> {
+        let new_ptr = Arc::into_raw(data.into());
+        match self.link.update_bounded(new_ptr, ArcCredit) {
+            Some((old_ptr, _gen)) => Ok(ptr_to_arc(old_ptr)),
+            None => Err(unsafe { Arc::from_raw(new_ptr) }),
+        }
+    }
+
+    /// Write a value to the rcu cell and defer the drop of the old value to
+    /// the next `rcu_cell::collect`, so a large old value is never dropped on
+    /// the writer path.
+    #[inline]
+    pub fn write_deferred(&self, data: impl Into<Arc<T>>)
+    where
+        T: Send + Sync + 'static,
+    {
+        if let Some(old) = self.write(data) {
+            crate::deferred::GLOBAL.defer(old);
+        }
+    }
+
+    /// write a value to the rcu cell and return the old value together with
+    /// the generation of this publication, see `set_gen`
+    #[inline]
+    pub fn write_gen(&self, data: impl Into<Arc<T>>) -> (Option<Arc<T>>, u64) {
+        let data = data.into();
+        self.set_gen(Some(data))
+    }
+
     /// Atomicly update the value with a closure and return the old value.
     /// The closure will be called with the old value and return the new value.
     /// The closure should not take too long time, internally it's use a spin
-    /// lock to prevent other writer to update the value
+    /// lock to prevent other writer to update the value, readers are never
+    /// blocked by it
     pub fn update<R, F>(&self, f: F) -> Option<Arc<T>>
     where
         F: FnOnce(Option<Arc<T>>) -> Option<R>,
         R: Into<Arc<T>>,
     {
-        // increase ref count to lock the inner Arc
-        let ptr = self.link.lock_read();
-        let old_value = ptr_to_arc(ptr);
-        let new_ptr = match f(old_value.clone()) {
+        self.update_gen(f).0
+    }
+
+    /// Atomicly update the value with a closure like `update`, but the
+    /// closure borrows the old value instead of getting a clone of its Arc.
+    /// The borrow is valid under the update lock, so the old Arc is only
+    /// returned and never cloned nor dropped by the update.
+    pub fn update_with_ref<R, F>(&self, f: F) -> Option<Arc<T>>
+    where
+        F: FnOnce(Option<&T>) -> Option<R>,
+        R: Into<Arc<T>>,
+    {
+        let lock = UpdateLock::new(&self.link);
+        let ptr = lock.ptr();
+        let old_value = ManuallyDrop::new(ptr_to_arc(ptr));
+        let new_ptr = match f(old_value.as_deref()) {
+            Some(data) => Arc::into_raw(data.into()),
+            None => ptr::null_mut(),
+        };
+        lock.unlock_update(new_ptr, ArcCredit);
+        ManuallyDrop::into_inner(old_value)
+    }
+
+    // Atomicly replace the value with the one returned by `f` if it's `Ok`,
+    // and return the old value, otherwise leave the rcu cell untouched
+    pub(crate) fn update_if<E, F>(&self, f: F) -> Result<Option<Arc<T>>, E>
+    where
+        F: FnOnce(Option<&Arc<T>>) -> Result<Option<Arc<T>>, E>,
+    {
+        let lock = UpdateLock::new(&self.link);
+        let ptr = lock.ptr();
+        let old_value = ManuallyDrop::new(ptr_to_arc(ptr));
+        match f(old_value.as_ref()) {
+            Ok(new_value) => {
+                lock.unlock_update(new_value.into_raw(), ArcCredit);
+                Ok(ManuallyDrop::into_inner(old_value))
+            }
+            Err(e) => {
+                lock.unlock();
+                Err(e)
+            }
+        }
+    }
+
+    /// Atomicly update the value with a closure like `update`, and return
+    /// the old value together with the generation of this publication,
+    /// see `set_gen`
+    pub fn update_gen<R, F>(&self, f: F) -> (Option<Arc<T>>, u64)
+    where
+        F: FnOnce(Option<Arc<T>>) -> Option<R>,
+        R: Into<Arc<T>>,
+    {
+        // the old value is still owned by the rcu cell if `f` panics
+        let lock = UpdateLock::new(&self.link);
+        let ptr = lock.ptr();
+        let old_value = ManuallyDrop::new(ptr_to_arc(ptr));
+        let new_ptr = match f((*old_value).clone()) {
             Some(data) => Arc::into_raw(data.into()),
             None => ptr::null_mut(),
         };
-        self.link.unlock_update(new_ptr);
-        old_value
+        let (_, gen) = lock.unlock_update(new_ptr, ArcCredit);
+        (ManuallyDrop::into_inner(old_value), gen)
+    }
+
+    /// Atomicly update the value with a closure like `update`, and refresh
+    /// the weak `mirror` of the rcu cell to the new value. Return both the
+    /// old value and the old weak value of the mirror.
+    ///
+    /// The mirror is refreshed under the update lock before the new value
+    /// is published, so once the new value can be read from the rcu cell,
+    /// the mirror never gives out a weak of the replaced value. The mirror
+    /// should only be written through this method.
+    pub fn update_weak<R, F>(&self, mirror: &RcuWeak<T>, f: F) -> (Option<Arc<T>>, Weak<T>)
+    where
+        F: FnOnce(Option<Arc<T>>) -> Option<R>,
+        R: Into<Arc<T>>,
+    {
+        let lock = UpdateLock::new(&self.link);
+        let ptr = lock.ptr();
+        let old_value = ManuallyDrop::new(ptr_to_arc(ptr));
+        let new_value = f((*old_value).clone()).map(Into::into);
+        let old_weak = match &new_value {
+            Some(v) => mirror.write_arc(v),
+            None => mirror.take(),
+        };
+        lock.unlock_update(new_value.into_raw(), ArcCredit);
+        (ManuallyDrop::into_inner(old_value), old_weak)
+    }
+
+    /// Atomicly update the value with a closure like `update`, but return
+    /// both the old value and the newly stored value.
+    /// The new value is the one installed by this call, even if other
+    /// writers replace it right after the update.
+    pub fn update_and_fetch<R, F>(&self, f: F) -> (Option<Arc<T>>, Option<Arc<T>>)
+    where
+        F: FnOnce(Option<Arc<T>>) -> Option<R>,
+        R: Into<Arc<T>>,
+    {
+        let lock = UpdateLock::new(&self.link);
+        let ptr = lock.ptr();
+        let old_value = ManuallyDrop::new(ptr_to_arc(ptr));
+        let new_value = f((*old_value).clone()).map(Into::into);
+        let new_ptr = new_value.clone().into_raw();
+        lock.unlock_update(new_ptr, ArcCredit);
+        (ManuallyDrop::into_inner(old_value), new_value)
+    }
+
+    /// Return the current value, if the rcu cell is empty build a new value
+    /// with `f` and store it. The check and the store are done under the
+    /// update lock, so racing threads never build the value twice, others
+    /// spin until the value is ready.
+    pub fn get_or_init<R, F>(&self, f: F) -> Arc<T>
+    where
+        F: FnOnce() -> R,
+        R: Into<Arc<T>>,
+    {
+        if let Some(v) = self.read() {
+            return v;
+        }
+        let lock = UpdateLock::new(&self.link);
+        let ptr = lock.ptr();
+        let old_value = ManuallyDrop::new(ptr_to_arc(ptr));
+        if let Some(v) = old_value.as_ref() {
+            let v = v.clone();
+            lock.unlock();
+            return v;
+        }
+        let value = f().into();
+        lock.unlock_update(Arc::into_raw(Arc::clone(&value)), ArcCredit);
+        value
+    }
+
+    /// Return the current value, if the rcu cell is empty store
+    /// `T::default()` into it, see `get_or_init`
+    #[inline]
+    pub fn get_or_default(&self) -> Arc<T>
+    where
+        T: Default,
+    {
+        self.get_or_init(T::default)
+    }
+
+    /// Write a value to the rcu cell only if it's empty and return the
+    /// stored value, otherwise return the rejected value together with the
+    /// current one. It turns the rcu cell into a single slot mailbox, the
+    /// first writer claims it until the value is taken.
+    pub fn write_if_none(&self, data: T) -> Result<Arc<T>, (T, Arc<T>)> {
+        if let Some(v) = self.read() {
+            return Err((data, v));
+        }
+        let lock = UpdateLock::new(&self.link);
+        let ptr = lock.ptr();
+        let old_value = ManuallyDrop::new(ptr_to_arc(ptr));
+        if let Some(v) = old_value.as_ref() {
+            let v = v.clone();
+            lock.unlock();
+            return Err((data, v));
+        }
+        let value = Arc::new(data);
+        lock.unlock_update(Arc::into_raw(Arc::clone(&value)), ArcCredit);
+        Ok(value)
+    }
+
+    /// Atomicly mutate the value with `Arc::make_mut` semantics.
+    /// If nobody else holds the stored Arc, the value is mutated in place
+    /// without any allocation, otherwise a clone of it is mutated and
+    /// published. Return false if the rcu cell is empty.
+    ///
+    /// Readers that arrive during the mutation spin until the new value is
+    /// published, so the closure should be as short as possible. The
+    /// unprotected readers are not waited for, see `read_unprotected`.
+    pub fn update_mut<F>(&self, f: F) -> bool
+    where
+        T: Clone,
+        F: FnOnce(&mut T),
+    {
+        let mut lock = UpdateLock::new(&self.link);
+        let ptr = lock.ptr();
+        if ptr.is_null() {
+            lock.unlock();
+            return false;
+        }
+        // a value that `f` panics on is left partly mutated in place
+        lock.lock_mut();
+        let mut old_value = ManuallyDrop::new(unsafe { Arc::from_raw(ptr) });
+        match Arc::get_mut(&mut old_value) {
+            Some(data) => {
+                f(data);
+                lock.unlock_update(ptr, ArcCredit);
+            }
+            None => {
+                let mut data = T::clone(&old_value);
+                f(&mut data);
+                let new_ptr = Arc::into_raw(Arc::new(data));
+                lock.unlock_update(new_ptr, ArcCredit);
+                let _ = ManuallyDrop::into_inner(old_value);
+            }
+        }
+        true
     }
 
     /// Stores the optional Arc ref `new` into the RcuCell if the current
@@ -183,17 +582,115 @@ impl<T> RcuCell<T> {
             None => ptr::null(),
         };
 
-        self.link
-            .compare_exchange(current, new_ptr, success, failure)
-            .inspect(|ptr| {
-                // drop the old arc in the rcu cell
-                let _ = ptr_to_arc(ptr);
-                // we have succeed to exchange the arc
-                if let Some(v) = new {
-                    // clone and forget the arc that hold by rcu cell
-                    let _ = Arc::into_raw(Arc::clone(v));
-                }
-            })
+        let ret = self
+            .link
+            .compare_exchange(current, new_ptr, success, failure, ArcCredit);
+        Self::exchanged(ret, new)
+    }
+
+    /// Stores the optional Arc ref `new` into the RcuCell if the current
+    /// value is the same as `current`, like `compare_exchange`, but it's
+    /// allowed to spuriously fail even when the comparison succeeds, like
+    /// when another writer holds the update lock, which is cheaper in a
+    /// retry loop.
+    ///
+    /// # Safety
+    ///
+    /// don't deref the returned pointer, it's may be dropped by other threads
+    pub unsafe fn compare_exchange_weak<'a>(
+        &self,
+        current: *const T,
+        new: Option<&'a Arc<T>>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<*const T, *const T>
+    where
+        T: 'a,
+    {
+        let new_ptr = match new {
+            Some(data) => Arc::as_ptr(data),
+            None => ptr::null(),
+        };
+
+        let ret = self
+            .link
+            .compare_exchange_weak(current, new_ptr, success, failure, ArcCredit);
+        Self::exchanged(ret, new)
+    }
+
+    // finish a successful exchange of the raw `compare_exchange`
+    #[inline]
+    fn exchanged(
+        ret: Result<(*const T, u64), *const T>,
+        new: Option<&Arc<T>>,
+    ) -> Result<*const T, *const T> {
+        ret.map(|(ptr, _gen)| {
+            // drop the old arc in the rcu cell
+            let _ = ptr_to_arc(ptr);
+            // we have succeed to exchange the arc
+            if let Some(v) = new {
+                // clone and forget the arc that hold by rcu cell
+                let _ = Arc::into_raw(Arc::clone(v));
+            }
+            ptr
+        })
+    }
+
+    /// Replace the value with `new` only if the rcu cell still holds exactly
+    /// the `expected` Arc, compared by pointer.
+    ///
+    /// Return the old value on success, otherwise give back `new`. It's the
+    /// safe form of `compare_exchange`, the `expected` Arc keeps its
+    /// allocation alive, so its address can't be reused by another value.
+    pub fn swap_if_arc_eq(
+        &self,
+        expected: &Arc<T>,
+        new: Option<Arc<T>>,
+    ) -> Result<Option<Arc<T>>, Option<Arc<T>>> {
+        let new_ptr = new.into_raw();
+        let ret = unsafe {
+            self.link.compare_exchange(
+                Arc::as_ptr(expected),
+                new_ptr,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                ArcCredit,
+            )
+        };
+        Self::swapped(ret, new_ptr)
+    }
+
+    /// Replace the value with `new` only if the rcu cell still holds exactly
+    /// the `expected` Arc like `swap_if_arc_eq`, but it's allowed to
+    /// spuriously fail, see `compare_exchange_weak`.
+    pub fn swap_if_arc_eq_weak(
+        &self,
+        expected: &Arc<T>,
+        new: Option<Arc<T>>,
+    ) -> Result<Option<Arc<T>>, Option<Arc<T>>> {
+        let new_ptr = new.into_raw();
+        let ret = unsafe {
+            self.link.compare_exchange_weak(
+                Arc::as_ptr(expected),
+                new_ptr,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                ArcCredit,
+            )
+        };
+        Self::swapped(ret, new_ptr)
+    }
+
+    // finish the exchange of `swap_if_arc_eq`, `new_ptr` is owned
+    #[inline]
+    fn swapped(
+        ret: Result<(*const T, u64), *const T>,
+        new_ptr: *const T,
+    ) -> Result<Option<Arc<T>>, Option<Arc<T>>> {
+        match ret {
+            Ok((old, _gen)) => Ok(ptr_to_arc(old)),
+            Err(_) => Err(ptr_to_arc(new_ptr)),
+        }
     }
 
     /// read out the inner Arc value
@@ -202,20 +699,407 @@ impl<T> RcuCell<T> {
         let ptr = self.link.inc_ref();
         let v = ManuallyDrop::new(ptr_to_arc(ptr));
         let cloned = v.as_ref().cloned();
-        self.link.dec_ref();
-        core::sync::atomic::fence(Ordering::Acquire);
+        dec_ref(&self.link, ptr);
+        fence(Ordering::Acquire);
         cloned
     }
 
+    /// read out a clone of the inner value, the Arc is released right
+    /// after the clone
+    #[inline]
+    pub fn read_clone(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.read().map(|v| T::clone(&v))
+    }
+
+    /// Read out the inner Arc value like `read`, with the memory ordering of
+    /// the load against the surrounding accesses, like `AtomicPtr::load`.
+    ///
+    /// The value itself is always synchronized with its publication, so it
+    /// can be safely used whatever the ordering is, `Relaxed` only drops the
+    /// acquire fence that `read` issues after it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`.
+    #[inline]
+    pub fn load(&self, order: Ordering) -> Option<Arc<T>> {
+        let ptr = self.link.inc_ref();
+        let v = ManuallyDrop::new(ptr_to_arc(ptr));
+        let cloned = v.as_ref().cloned();
+        dec_ref(&self.link, ptr);
+        match order {
+            Ordering::Relaxed => {}
+            Ordering::Acquire => fence(Ordering::Acquire),
+            Ordering::SeqCst => fence(Ordering::SeqCst),
+            Ordering::Release => panic!("there is no such thing as a release load"),
+            Ordering::AcqRel => panic!("there is no such thing as an acquire-release load"),
+            _ => fence(Ordering::SeqCst),
+        }
+        cloned
+    }
+
+    /// Read out the inner Arc value like `read`, but never wait or panic.
+    ///
+    /// Return `Err(WouldBlock)` if the value is being mutated in place by
+    /// `update_mut` or there are too many in-flight readers.
+    #[cfg(feature = "panic-free")]
+    #[inline]
+    pub fn try_read(&self) -> Result<Option<Arc<T>>, WouldBlock> {
+        self.read_nowait().ok_or(WouldBlock)
+    }
+
+    // read out the inner Arc value without waiting,
+    // return `None` if `read` would wait
+    #[inline]
+    fn read_nowait(&self) -> Option<Option<Arc<T>>> {
+        let ptr = self.link.try_inc_ref()?;
+        let v = ManuallyDrop::new(ptr_to_arc(ptr));
+        let cloned = v.as_ref().cloned();
+        dec_ref(&self.link, ptr);
+        fence(Ordering::Acquire);
+        Some(cloned)
+    }
+
+    /// Write an option arc value to the rcu cell like `set`, but never
+    /// wait for other writers or panic.
+    ///
+    /// Return the old value on success, otherwise give back `data` if
+    /// another writer is updating the rcu cell, on 32-bit platforms also if
+    /// any reader is in flight.
+    #[cfg(feature = "panic-free")]
+    #[inline]
+    pub fn try_set(&self, data: Option<Arc<T>>) -> Result<Option<Arc<T>>, Option<Arc<T>>> {
+        let new_ptr = data.into_raw();
+        self.try_swap(new_ptr).ok_or_else(|| ptr_to_arc(new_ptr))
+    }
+
+    /// Write a value to the rcu cell like `write`, but never wait for other
+    /// writers or panic, see `try_set`.
+    #[cfg(feature = "panic-free")]
+    #[inline]
+    pub fn try_write(&self, data: impl Into<Arc<T>>) -> Result<Option<Arc<T>>, Arc<T>> {
+        let new_ptr = Arc::into_raw(data.into());
+        self.try_swap(new_ptr)
+            .ok_or_else(|| unsafe { Arc::from_raw(new_ptr) })
+    }
+
+    // publish `new_ptr` and return the old value, or None if another
+    // writer is updating the rcu cell, then `new_ptr` is not consumed
+    #[cfg(feature = "panic-free")]
+    #[inline]
+    fn try_swap(&self, new_ptr: *const T) -> Option<Option<Arc<T>>> {
+        let (old_ptr, _gen) = self.link.try_update(new_ptr, ArcCredit)?;
+        Some(ptr_to_arc(old_ptr))
+    }
+
+    /// Return the version of the rcu cell, it's the generation of the latest
+    /// publication and increases on every `set`/`write`/`update`.
+    /// Load the version before reading the value, then a later different
+    /// version means the value may have changed since.
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.link.gen()
+    }
+
+    /// Block the thread until the stored value satisfies `pred` and return
+    /// it, `pred` is checked with the current value and then after every
+    /// publication. The thread is parked between the publications.
+    #[cfg(feature = "std")]
+    pub fn wait_until<F>(&self, mut pred: F) -> Option<Arc<T>>
+    where
+        F: FnMut(Option<&T>) -> bool,
+    {
+        loop {
+            // load the version before reading, so a publication after the
+            // read always wakes us up
+            let version = self.version();
+            let value = self.read();
+            if pred(value.as_deref()) {
+                return value;
+            }
+            drop(value);
+            park::wait_until(self.key(), || self.version() != version);
+        }
+    }
+
+    /// Block the thread until the rcu cell is not empty and return the
+    /// value, or return `None` if it's still empty after the `timeout`.
+    /// It lets a reader start before the writer publishes the first value.
+    #[cfg(feature = "std")]
+    pub fn wait_for_some(&self, timeout: Option<Duration>) -> Option<Arc<T>> {
+        // a timeout too large to represent is no timeout
+        let Some(deadline) = timeout.and_then(|t| Instant::now().checked_add(t)) else {
+            return self.wait_until(|v| v.is_some());
+        };
+        loop {
+            let version = self.version();
+            let value = self.read();
+            if value.is_some() {
+                return value;
+            }
+            if !park::wait_until_deadline(self.key(), || self.version() != version, deadline) {
+                return self.read();
+            }
+        }
+    }
+
+    /// Return a future that resolves on the next publication to the rcu
+    /// cell after this call, read the new value after it resolves.
+    #[cfg(feature = "async")]
+    #[inline]
+    pub fn changed(&self) -> Changed<'_, T> {
+        Changed::new(self)
+    }
+
+    /// Return a stream of the values of the rcu cell, it yields the current
+    /// value first and then each newly published value, the intermediate
+    /// ones published between two polls are skipped.
+    #[cfg(feature = "async")]
+    #[inline]
+    pub fn subscribe(&self) -> Subscription<'_, T> {
+        Subscription::new(self)
+    }
+
+    /// Return a stream of the values of the rcu cell that only yields the
+    /// transitions accepted by `pred(old, new)`, e.g. a flag flipping in a
+    /// frequently updated snapshot. The current value is the first `old`.
+    #[cfg(feature = "async")]
+    #[inline]
+    pub fn subscribe_filtered<F>(&self, pred: F) -> FilteredSubscription<'_, T, F>
+    where
+        F: FnMut(Option<&T>, Option<&T>) -> bool,
+    {
+        FilteredSubscription::new(self, pred)
+    }
+
+    /// Return a stream of a projection of the values of the rcu cell, it
+    /// yields the current projection first and then only the projections
+    /// not equal to the last yielded one, e.g. one field of a frequently
+    /// rewritten config. To dedup by pointer, project into a type whose
+    /// `PartialEq` compares the pointers, like `Arc::ptr_eq`.
+    #[cfg(feature = "async")]
+    #[inline]
+    pub fn watch_map<U, F>(&self, map: F) -> WatchMap<'_, T, U, F>
+    where
+        F: FnMut(Option<&T>) -> U,
+        U: PartialEq + Clone,
+    {
+        WatchMap::new(self, map)
+    }
+
+    /// Wait until the stored value satisfies `pred` and return it, `pred`
+    /// is checked with the current value and then after every publication.
+    #[cfg(feature = "async")]
+    pub async fn wait_for<F>(&self, mut pred: F) -> Option<Arc<T>>
+    where
+        F: FnMut(Option<&T>) -> bool,
+    {
+        loop {
+            // subscribe before reading, so a publication after the read
+            // always resolves the future
+            let changed = self.changed();
+            let value = self.read();
+            if pred(value.as_deref()) {
+                return value;
+            }
+            drop(value);
+            changed.await;
+        }
+    }
+
+    // the key of the threads and tasks waiting for the next publication
+    #[inline]
+    pub(crate) fn key(&self) -> usize {
+        self.link.key()
+    }
+
+    /// Return a stable identity of the current value that is safe to log,
+    /// two calls return the same id only if they see the same publication.
+    #[inline]
+    pub fn identity(&self) -> ValueId {
+        self.link.identity()
+    }
+
+    /// read out a `Weak` to the inner value without cloning the Arc,
+    /// return a dangling `Weak` if the rcu cell is empty
+    #[inline]
+    pub fn downgrade(&self) -> Weak<T> {
+        let ptr = self.link.inc_ref();
+        let v = ManuallyDrop::new(ptr_to_arc(ptr));
+        let weak = v.as_ref().map(Arc::downgrade).unwrap_or_default();
+        dec_ref(&self.link, ptr);
+        fence(Ordering::Acquire);
+        weak
+    }
+
+    // call `f` with a reference to the current value under the reader
+    // count, the refcount of the Arc is never touched
+    #[inline]
+    pub(crate) fn with_ref<R>(&self, f: impl FnOnce(Option<&T>) -> R) -> R {
+        let ptr = self.link.inc_ref();
+        let _guard = ReadGuard(&self.link, ptr);
+        let v = ManuallyDrop::new(ptr_to_arc(ptr));
+        f(v.as_deref())
+    }
+
+    /// read out the inner Arc value into `out`, reusing the slot.
+    /// If `out` already holds the current value it is left untouched,
+    /// so polling loops pay no refcount traffic for unchanged cells.
+    #[inline]
+    pub fn read_into(&self, out: &mut Option<Arc<T>>) {
+        let ptr = self.link.inc_ref();
+        let old = (out.as_ptr() != ptr).then(|| {
+            let v = ManuallyDrop::new(ptr_to_arc(ptr));
+            core::mem::replace(out, v.as_ref().cloned())
+        });
+        dec_ref(&self.link, ptr);
+        fence(Ordering::Acquire);
+        // drop the old value after releasing the reader count
+        drop(old);
+    }
+
+    /// Read the inner pointer without any reader protection, it's a single
+    /// acquire load that treats the cell as a plain published pointer.
+    /// Return null if the rcu cell is empty.
+    ///
+    /// # Safety
+    ///
+    /// The reader count is not taken, so nothing stops a writer from
+    /// dropping the value while the pointer is in use. The caller must run
+    /// its own reclamation scheme (e.g. QSBR) which guarantees that the last
+    /// `Arc` of a replaced value is only dropped after every read-side
+    /// critical section that may have loaded it has ended.
+    ///
+    /// `update_mut` must not run while any unprotected reader may be in
+    /// flight, it mutates the value in place once the protected readers are
+    /// drained, and it can't see the unprotected ones, so they would race
+    /// with the mutation.
+    #[inline]
+    pub unsafe fn read_unprotected(&self) -> *const T {
+        self.link.get_ref()
+    }
+
+    /// Read the inner value as a reference without any reader protection,
+    /// return `None` if the rcu cell is empty.
+    ///
+    /// # Safety
+    ///
+    /// Same as `read_unprotected`, in addition the returned reference must
+    /// not be used after the read-side critical section ends, though the
+    /// lifetime only ties it to the cell.
+    #[inline]
+    pub unsafe fn deref_unprotected(&self) -> Option<&T> {
+        self.read_unprotected().as_ref()
+    }
+
+    /// The pointer to the current value, null if the rcu cell is empty.
+    ///
+    /// It's only meant for identity comparison and change detection, never
+    /// deref it, the value may be dropped by a writer at any time. The
+    /// address may also be reused by a later value once the old one is
+    /// dropped, see `identity` for an id that is never reused.
+    #[inline]
+    pub fn as_ptr(&self) -> *const T {
+        self.link.get_ref()
+    }
+
     /// read inner ptr and check if it is the same as the given Arc
     #[inline]
     pub fn arc_eq(&self, data: &Arc<T>) -> bool {
         self.link.get_ref() == Arc::as_ptr(data)
     }
 
+    /// Exchange the values of two rcu cells. Both update locks are taken in
+    /// address order, so other writers never observe or lose a value in
+    /// the middle of the swap.
+    pub fn swap_cells(a: &Self, b: &Self) {
+        if ptr::eq(a, b) {
+            return;
+        }
+        let (first, second) = if (a as *const Self) < (b as *const Self) {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let first_ptr = first.link.lock_read();
+        let second_ptr = second.link.lock_read();
+        // the ownership of the inner Arcs is simply exchanged
+        first.link.unlock_update(second_ptr, ArcCredit);
+        second.link.unlock_update(first_ptr, ArcCredit);
+    }
+
     /// check if two RcuCell instances point to the same inner Arc
     #[inline]
     pub fn ptr_eq(this: &Self, other: &Self) -> bool {
         this.link.get_ref() == other.link.get_ref()
     }
+
+    /// Hash the address of the inner Arc, it pairs with `ptr_eq` to key the
+    /// cells by the identity of their values, like in a `HashSet` wrapper.
+    /// The hash changes with every write, so don't write the keyed cells.
+    #[inline]
+    pub fn ptr_hash<H: Hasher>(&self, state: &mut H) {
+        self.link.get_ref().hash(state);
+    }
+
+    /// Decode the packed word of the cell into a structured snapshot.
+    /// Handy when debugging hangs from logs or core dumps.
+    #[inline]
+    pub fn debug_state(&self) -> CellState {
+        self.link.state()
+    }
+
+    /// The number of the readers in flight, a racy snapshot for logging and
+    /// alerting on reader pile-ups. With the packed layout it also counts
+    /// the readers of a replaced value that are not released yet.
+    #[inline]
+    pub fn reader_count(&self) -> usize {
+        self.link.state().readers
+    }
+
+    /// The contention counters of the cell since it's created, they help
+    /// to decide whether a hot cell should be sharded.
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub fn stats(&self) -> ContentionStats {
+        self.link.stats()
+    }
+
+    /// Check if a writer holds the update lock, a racy snapshot for
+    /// spotting stuck updaters, it's legitimately set while another thread
+    /// is running `update`.
+    #[inline]
+    pub fn is_update_locked(&self) -> bool {
+        self.link.state().updating
+    }
+
+    /// Decode the packed word of the cell and cross check its invariants,
+    /// so long-running processes can self-audit after suspected memory
+    /// corruption.
+    ///
+    /// The result is a racy snapshot, `update_locked` is legitimately set
+    /// while another thread is running `update`.
+    #[inline]
+    pub fn validate(&self) -> Validation {
+        self.link.validate()
+    }
+
+    /// Project an `Option<U>` field of the stored value as if it were its
+    /// own cell. `get` borrows the field from the value, and `set` writes
+    /// it into a clone of the value that is published as the new parent.
+    #[inline]
+    pub fn split_option<U>(
+        &self,
+        get: fn(&T) -> &Option<U>,
+        set: fn(&mut T, Option<U>),
+    ) -> RcuOptionField<'_, T, U>
+    where
+        T: Clone,
+        U: Clone,
+    {
+        RcuOptionField::new(self, get, set)
+    }
 }
@@ -1,9 +1,11 @@
 use alloc::sync::{Arc, Weak};
+use core::fmt;
 use core::mem::ManuallyDrop;
 use core::ptr;
 use core::sync::atomic::Ordering;
 
-use crate::link::LinkWrapper;
+use crate::link::{Credit, LinkWrapper, UpdateLock};
+use crate::sync::{const_fn, fence};
 
 #[inline]
 fn ptr_to_weak<T>(ptr: *const T) -> Weak<T> {
@@ -14,12 +16,61 @@ fn ptr_to_weak<T>(ptr: *const T) -> Weak<T> {
     }
 }
 
+#[inline]
+fn weak_to_ptr<T>(data: Weak<T>) -> *const T {
+    if data.ptr_eq(&Weak::new()) {
+        ptr::null()
+    } else {
+        Weak::into_raw(data)
+    }
+}
+
+// credit the counts of the laggard readers to the swapped out Weak,
+// see `Credit`
+struct WeakCredit;
+
+impl<T> Credit<T> for WeakCredit {
+    #[inline]
+    unsafe fn credit(&self, ptr: *const T, n: isize) {
+        let weak = ManuallyDrop::new(Weak::from_raw(ptr));
+        for _ in 0..n {
+            let _ = ManuallyDrop::new(Weak::clone(&weak));
+        }
+        for _ in n..0 {
+            drop(Weak::from_raw(ptr));
+        }
+    }
+}
+
+// take the ownership of the swapped out Weak
+#[inline]
+fn swapped_out<T>((ptr, _gen): (*const T, u64)) -> Weak<T> {
+    ptr_to_weak(ptr)
+}
+
+// release the reader count of `ptr`, if a writer has credited the count
+// to the Weak, release that weak count instead
+#[inline]
+fn dec_ref<T>(link: &LinkWrapper<T>, ptr: *const T) {
+    if link.dec_ref(ptr) {
+        drop(unsafe { Weak::from_raw(ptr) });
+    }
+}
+
 /// RCU weak cell, it behaves like `RwLock<Weak<T>>`
-#[derive(Debug)]
 pub struct RcuWeak<T> {
     link: LinkWrapper<T>,
 }
 
+// show the upgraded value, `None` if the cell is empty or the value is dead
+impl<T: fmt::Debug> fmt::Debug for RcuWeak<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RcuWeak")
+            .field("value", &self.upgrade())
+            .finish()
+    }
+}
+
 unsafe impl<T: Send + Sync> Send for RcuWeak<T> {}
 unsafe impl<T: Send + Sync> Sync for RcuWeak<T> {}
 
@@ -38,7 +89,7 @@ impl<T> Default for RcuWeak<T> {
 
 impl<T> From<Weak<T>> for RcuWeak<T> {
     fn from(data: Weak<T>) -> Self {
-        let weak_ptr = Weak::into_raw(data);
+        let weak_ptr = weak_to_ptr(data);
         RcuWeak {
             link: LinkWrapper::new(weak_ptr),
         }
@@ -46,11 +97,13 @@ impl<T> From<Weak<T>> for RcuWeak<T> {
 }
 
 impl<T> RcuWeak<T> {
-    /// create an dummy rcu weak cell instance, upgrade from it will return None
-    #[inline]
-    pub const fn new() -> Self {
-        RcuWeak {
-            link: LinkWrapper::new(ptr::null()),
+    const_fn! {
+        /// create an dummy rcu weak cell instance, upgrade from it will return None
+        #[inline]
+        pub const fn new() -> Self {
+            RcuWeak {
+                link: LinkWrapper::none(),
+            }
         }
     }
 
@@ -63,21 +116,37 @@ impl<T> RcuWeak<T> {
         ret
     }
 
+    /// Consume the rcu weak and return the raw pointer of the inner `Weak`,
+    /// it's null if the rcu weak holds a dangling `Weak`. Use `from_raw` to
+    /// turn it back into a rcu weak, or the weak count is leaked.
+    #[inline]
+    pub fn into_raw(self) -> *const T {
+        weak_to_ptr(self.into_weak())
+    }
+
+    /// Create a rcu weak from a raw pointer returned by `into_raw`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null, or returned by `RcuWeak::into_raw` or
+    /// `Weak::into_raw`, and its weak count is owned by the rcu weak from
+    /// now on, like `Weak::from_raw`.
+    #[inline]
+    pub unsafe fn from_raw(ptr: *const T) -> Self {
+        RcuWeak::from(ptr_to_weak(ptr))
+    }
+
     /// take the value from the rcu weak, leave the rcu weak with default value
     #[inline]
     pub fn take(&self) -> Weak<T> {
-        ptr_to_weak(self.link.update(ptr::null()))
+        swapped_out(self.link.update(ptr::null(), WeakCredit))
     }
 
     /// write a new weak value to the rcu weak cell and return the old value
     #[inline]
     pub fn write(&self, data: Weak<T>) -> Weak<T> {
-        let new_ptr = if data.ptr_eq(&Weak::new()) {
-            ptr::null()
-        } else {
-            Weak::into_raw(data)
-        };
-        ptr_to_weak(self.link.update(new_ptr))
+        let new_ptr = weak_to_ptr(data);
+        swapped_out(self.link.update(new_ptr, WeakCredit))
     }
 
     /// write a new `Weak` value downgrade from the `Arc`` to the cell and return the old value
@@ -85,7 +154,107 @@ impl<T> RcuWeak<T> {
     pub fn write_arc(&self, data: &Arc<T>) -> Weak<T> {
         let weak = Arc::downgrade(data);
         let new_ptr = Weak::into_raw(weak);
-        ptr_to_weak(self.link.update(new_ptr))
+        swapped_out(self.link.update(new_ptr, WeakCredit))
+    }
+
+    /// Atomicly update the weak value with a closure and return the old value.
+    /// The closure will be called with the old value and return the new value.
+    /// The closure should not take too long time, internally it's use a spin
+    /// lock to prevent other writer to update the value
+    pub fn update<F>(&self, f: F) -> Weak<T>
+    where
+        F: FnOnce(Weak<T>) -> Weak<T>,
+    {
+        let lock = UpdateLock::new(&self.link);
+        let ptr = lock.ptr();
+        let old_value = ManuallyDrop::new(ptr_to_weak(ptr));
+        let new_ptr = weak_to_ptr(f((*old_value).clone()));
+        lock.unlock_update(new_ptr, WeakCredit);
+        ManuallyDrop::into_inner(old_value)
+    }
+
+    /// Stores the `new` weak value into the cell if the current value is
+    /// the same as `current`, two `Weak` are the same if they point to the
+    /// same allocation, and all the dangling `Weak` are the same.
+    ///
+    /// Return the old value on success, otherwise give back `new`.
+    pub fn compare_exchange(&self, current: &Weak<T>, new: Weak<T>) -> Result<Weak<T>, Weak<T>> {
+        let current_ptr = if current.ptr_eq(&Weak::new()) {
+            ptr::null()
+        } else {
+            Weak::as_ptr(current)
+        };
+        let new_ptr = weak_to_ptr(new);
+        // the allocation is kept by `current`, so the address can't be reused
+        let ret = unsafe {
+            self.link.compare_exchange(
+                current_ptr,
+                new_ptr,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                WeakCredit,
+            )
+        };
+        match ret {
+            Ok(old) => Ok(swapped_out(old)),
+            Err(_) => Err(ptr_to_weak(new_ptr)),
+        }
+    }
+
+    /// Stores a clone of the `new` weak value into the cell if the current
+    /// value is the raw pointer `current`, like `RcuCell::compare_exchange`.
+    /// A null `current` stands for a dangling `Weak`. It doesn't need a
+    /// `Weak` of the current value, the pointer from `as_ptr` is enough.
+    ///
+    /// Return the old pointer on success, otherwise the current pointer.
+    ///
+    /// # Safety
+    ///
+    /// don't deref the returned pointer, the value and even its allocation
+    /// may be dropped by other threads. Once the allocation of `current` is
+    /// freed, its address may be reused by another value that the exchange
+    /// then succeeds against, hold a `Weak` of it to rule that out.
+    pub unsafe fn compare_exchange_raw(
+        &self,
+        current: *const T,
+        new: &Weak<T>,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<*const T, *const T> {
+        let new_ptr = weak_to_ptr(new.clone());
+        let ret = self
+            .link
+            .compare_exchange(current, new_ptr, success, failure, WeakCredit);
+        match ret {
+            Ok(old) => {
+                // drop the old weak value in the cell
+                drop(swapped_out(old));
+                Ok(current)
+            }
+            Err(actual) => {
+                drop(ptr_to_weak(new_ptr));
+                Err(actual)
+            }
+        }
+    }
+
+    /// Store the downgrade of `data` into the cell only if the current weak
+    /// value can no longer be upgraded, so a still alive cached value is
+    /// never stomped.
+    ///
+    /// Return `Ok` with `data` if the store happened, otherwise `Err` with
+    /// the surviving Arc.
+    pub fn write_arc_if_dead(&self, data: &Arc<T>) -> Result<Arc<T>, Arc<T>> {
+        let ptr = self.link.lock_read();
+        let old_value = ptr_to_weak(ptr);
+        if let Some(v) = old_value.upgrade() {
+            let _ = ManuallyDrop::new(old_value);
+            self.link.unlock();
+            return Err(v);
+        }
+        let new_ptr = Weak::into_raw(Arc::downgrade(data));
+        self.link.unlock_update(new_ptr, WeakCredit);
+        Ok(data.clone())
     }
 
     /// read out the inner weak value
@@ -94,8 +263,8 @@ impl<T> RcuWeak<T> {
         let ptr = self.link.inc_ref();
         let v = ManuallyDrop::new(ptr_to_weak(ptr));
         let cloned = (*v).clone();
-        self.link.dec_ref();
-        core::sync::atomic::fence(Ordering::Acquire);
+        dec_ref(&self.link, ptr);
+        fence(Ordering::Acquire);
         cloned
     }
 
@@ -105,11 +274,53 @@ impl<T> RcuWeak<T> {
         let ptr = self.link.inc_ref();
         let v = ManuallyDrop::new(ptr_to_weak(ptr));
         let cloned = v.upgrade();
-        self.link.dec_ref();
-        core::sync::atomic::fence(Ordering::Acquire);
+        dec_ref(&self.link, ptr);
+        fence(Ordering::Acquire);
         cloned
     }
 
+    /// Upgrade the inner weak value and call `f` with the value if it's
+    /// still alive. The temporary Arc is dropped before returning, so no
+    /// strong reference escapes to keep the value alive.
+    #[inline]
+    pub fn upgrade_with<R>(&self, f: impl FnOnce(Option<&T>) -> R) -> R {
+        let v = self.upgrade();
+        f(v.as_deref())
+    }
+
+    /// Upgrade the inner weak value, if it's dead build a new Arc with `f`
+    /// and store its downgrade into the cell. The check and the store are
+    /// done under the update lock, so racing threads never build the value
+    /// twice, others spin until the value is ready.
+    pub fn upgrade_or_init<F>(&self, f: F) -> Arc<T>
+    where
+        F: FnOnce() -> Arc<T>,
+    {
+        if let Some(v) = self.upgrade() {
+            return v;
+        }
+        let lock = UpdateLock::new(&self.link);
+        let ptr = lock.ptr();
+        let old_value = ManuallyDrop::new(ptr_to_weak(ptr));
+        if let Some(v) = old_value.upgrade() {
+            lock.unlock();
+            return v;
+        }
+        let value = f();
+        let new_ptr = Weak::into_raw(Arc::downgrade(&value));
+        lock.unlock_update(new_ptr, WeakCredit);
+        drop(ManuallyDrop::into_inner(old_value));
+        value
+    }
+
+    /// Return the raw pointer of the inner `Weak`, it's null if the cell
+    /// holds a dangling `Weak`. It's only for the identity comparisons and
+    /// `compare_exchange_raw`, the value may be dropped at any time.
+    #[inline]
+    pub fn as_ptr(&self) -> *const T {
+        self.link.get_ref()
+    }
+
     /// read inner ptr and check if it is the same as the given Arc
     #[inline]
     pub fn arc_eq(&self, data: &Arc<T>) -> bool {
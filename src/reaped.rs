@@ -0,0 +1,175 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::fmt;
+use core::mem::ManuallyDrop;
+use core::ops::Deref;
+use std::sync::mpsc::{self, Sender};
+use std::sync::OnceLock;
+
+use crate::RcuCell;
+
+type Garbage = Box<dyn Send>;
+
+// drop the value on the reaper thread, which is spawned on the first use,
+// or drop it in place if the thread can't be spawned
+fn reap<T: Send + 'static>(value: T) {
+    if !core::mem::needs_drop::<T>() {
+        return;
+    }
+    static REAPER: OnceLock<Option<Sender<Garbage>>> = OnceLock::new();
+    let reaper = REAPER.get_or_init(|| {
+        let (tx, rx) = mpsc::channel::<Garbage>();
+        std::thread::Builder::new()
+            .name("rcu-reaper".into())
+            .spawn(move || rx.into_iter().for_each(drop))
+            .ok()
+            .map(|_| tx)
+    });
+    if let Some(tx) = reaper {
+        // a value is given back and dropped here only if the reaper thread
+        // died of a panicking drop
+        let _ = tx.send(Box::new(value));
+    }
+}
+
+/// RCU cell whose replaced values are dropped on a background thread, built
+/// by `RcuCell::with_background_drop`
+///
+/// The reads and the old values of the writes are `ReapedRef` guards, the
+/// guard that holds the last reference of a value ships the value to a
+/// dedicated reaper thread instead of dropping it, so neither a writer nor
+/// a reader ever pays for the drop of a large value.
+pub struct ReapedRcuCell<T: Send + 'static> {
+    cell: RcuCell<T>,
+}
+
+impl<T: Send + 'static> fmt::Debug for ReapedRcuCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReapedRcuCell")
+            .field("cell", &self.cell.debug_state())
+            .finish()
+    }
+}
+
+impl<T: Send + 'static> Drop for ReapedRcuCell<T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.cell.take() {
+            drop(ReapedRef::new(value));
+        }
+    }
+}
+
+/// A read value of a `ReapedRcuCell`, the value is dropped on the reaper
+/// thread if the guard holds its last reference
+pub struct ReapedRef<T: Send + 'static> {
+    arc: ManuallyDrop<Arc<T>>,
+}
+
+impl<T: Send + 'static> ReapedRef<T> {
+    #[inline]
+    fn new(arc: Arc<T>) -> Self {
+        ReapedRef {
+            arc: ManuallyDrop::new(arc),
+        }
+    }
+
+    /// convert the guard to the inner Arc, its last reference is then
+    /// dropped in place as usual
+    #[inline]
+    pub fn into_arc(self) -> Arc<T> {
+        let mut this = ManuallyDrop::new(self);
+        unsafe { ManuallyDrop::take(&mut this.arc) }
+    }
+}
+
+impl<T: Send + 'static> Drop for ReapedRef<T> {
+    fn drop(&mut self) {
+        let arc = unsafe { ManuallyDrop::take(&mut self.arc) };
+        if let Some(value) = Arc::into_inner(arc) {
+            reap(value);
+        }
+    }
+}
+
+impl<T: Send + 'static> Deref for ReapedRef<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.arc
+    }
+}
+
+impl<T: Send + 'static> Clone for ReapedRef<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        ReapedRef::new(Arc::clone(&self.arc))
+    }
+}
+
+impl<T: Send + fmt::Debug + 'static> fmt::Debug for ReapedRef<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Send + 'static> ReapedRcuCell<T> {
+    #[inline]
+    pub(crate) fn new(cell: RcuCell<T>) -> Self {
+        ReapedRcuCell { cell }
+    }
+
+    /// return the inner rcu cell, the values are dropped in place again
+    #[inline]
+    pub fn into_inner(self) -> RcuCell<T> {
+        let this = ManuallyDrop::new(self);
+        unsafe { core::ptr::read(&this.cell) }
+    }
+
+    /// check if the rcu cell is empty
+    #[inline]
+    pub fn is_none(&self) -> bool {
+        self.cell.is_none()
+    }
+
+    /// read out the value
+    #[inline]
+    pub fn read(&self) -> Option<ReapedRef<T>> {
+        self.cell.read().map(ReapedRef::new)
+    }
+
+    /// the version of the rcu cell, see `RcuCell::version`
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.cell.version()
+    }
+
+    /// write an option arc value to the rcu cell and return the old value
+    #[inline]
+    pub fn set(&self, data: Option<Arc<T>>) -> Option<ReapedRef<T>> {
+        self.cell.set(data).map(ReapedRef::new)
+    }
+
+    /// take the value from the rcu cell, leave the rcu cell empty
+    #[inline]
+    pub fn take(&self) -> Option<ReapedRef<T>> {
+        self.set(None)
+    }
+
+    /// write a value to the rcu cell and return the old value
+    #[inline]
+    pub fn write(&self, data: impl Into<Arc<T>>) -> Option<ReapedRef<T>> {
+        self.set(Some(data.into()))
+    }
+
+    /// Atomicly update the value with a closure and return the old value,
+    /// see `RcuCell::update`
+    #[inline]
+    pub fn update<R, F>(&self, f: F) -> Option<ReapedRef<T>>
+    where
+        F: FnOnce(Option<Arc<T>>) -> Option<R>,
+        R: Into<Arc<T>>,
+    {
+        self.cell.update(f).map(ReapedRef::new)
+    }
+}
@@ -0,0 +1,102 @@
+use alloc::sync::Arc;
+#[cfg(not(feature = "portable-atomic"))]
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+#[cfg(feature = "portable-atomic")]
+use portable_atomic::AtomicU64;
+
+use crate::RcuCell;
+
+struct Entry<T> {
+    seq: u64,
+    value: Arc<T>,
+}
+
+/// A bounded broadcast history of the last `N` published values.
+///
+/// Every value published by `push` gets a sequence number, readers can
+/// fetch the latest value or any of the last `N` values by its sequence
+/// number without blocking the writers.
+pub struct RcuRing<T, const N: usize> {
+    slots: [RcuCell<Entry<T>>; N],
+    // the next sequence number to assign
+    next: AtomicU64,
+    // one past the highest sequence number that is published
+    published: AtomicU64,
+}
+
+impl<T, const N: usize> core::fmt::Debug for RcuRing<T, N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("RcuRing")
+            .field("capacity", &N)
+            .field("published", &self.published.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl<T, const N: usize> Default for RcuRing<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> RcuRing<T, N> {
+    /// create an empty ring
+    pub fn new() -> Self {
+        const { assert!(N > 0, "RcuRing capacity must not be zero") };
+        RcuRing {
+            slots: core::array::from_fn(|_| RcuCell::none()),
+            next: AtomicU64::new(0),
+            published: AtomicU64::new(0),
+        }
+    }
+
+    // the slot of a sequence number, the modulo is taken on the full
+    // sequence, a truncated one would wrap out of order on 32-bit targets
+    #[inline]
+    fn slot(&self, seq: u64) -> &RcuCell<Entry<T>> {
+        &self.slots[(seq % N as u64) as usize]
+    }
+
+    /// publish a value and return its sequence number, the oldest value
+    /// in the ring is evicted
+    pub fn push(&self, data: impl Into<Arc<T>>) -> u64 {
+        let seq = self.next.fetch_add(1, Ordering::Relaxed);
+        let mut entry = Some(Entry {
+            seq,
+            value: data.into(),
+        });
+        // a slower writer must not overwrite a newer value of the same slot
+        self.slot(seq).update(|old| match old {
+            Some(old) if old.seq > seq => Some(old),
+            _ => entry.take().map(Arc::new),
+        });
+        self.published.fetch_max(seq + 1, Ordering::Release);
+        seq
+    }
+
+    /// read the value with the given sequence number, return `None` if it's
+    /// not published yet or already evicted
+    pub fn get(&self, seq: u64) -> Option<Arc<T>> {
+        let entry = self.slot(seq).read()?;
+        (entry.seq == seq).then(|| entry.value.clone())
+    }
+
+    /// read the latest published value together with its sequence number
+    pub fn latest(&self) -> Option<(u64, Arc<T>)> {
+        loop {
+            let seq = self.published.load(Ordering::Acquire).checked_sub(1)?;
+            if let Some(v) = self.get(seq) {
+                return Some((seq, v));
+            }
+            // the slot is already reused by a newer value, try again
+        }
+    }
+
+    /// one past the sequence number of the latest published value,
+    /// values in `end - N .. end` are the ones still in the ring
+    #[inline]
+    pub fn end(&self) -> u64 {
+        self.published.load(Ordering::Acquire)
+    }
+}
@@ -0,0 +1,112 @@
+use alloc::sync::Arc;
+use core::marker::PhantomData;
+use core::ops::Deref;
+
+use crate::sync::const_fn;
+use crate::RcuCell;
+
+/// RCU cell whose value may borrow from an enclosing scope
+///
+/// Unlike `RcuCell`, the inner `Arc` never escapes: reads return a
+/// `ScopedRef` guard that borrows the cell, so all the snapshots must be
+/// dropped before the cell (and thus the `'scope` it borrows from) ends.
+/// This makes it usable inside `std::thread::scope` without forcing the
+/// stored value to be `'static`.
+#[derive(Debug)]
+pub struct ScopedRcuCell<'scope, T: 'scope> {
+    cell: RcuCell<T>,
+    scope: PhantomData<&'scope ()>,
+}
+
+/// A read snapshot of a `ScopedRcuCell`, bound to the borrow of the cell
+#[derive(Debug)]
+pub struct ScopedRef<'a, T> {
+    arc: Arc<T>,
+    cell: PhantomData<&'a ()>,
+}
+
+impl<T> Deref for ScopedRef<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.arc
+    }
+}
+
+impl<T> Default for ScopedRcuCell<'_, T> {
+    fn default() -> Self {
+        ScopedRcuCell::none()
+    }
+}
+
+impl<'scope, T: 'scope> ScopedRcuCell<'scope, T> {
+    const_fn! {
+        /// create an empty scoped rcu cell instance
+        #[inline]
+        pub const fn none() -> Self {
+            ScopedRcuCell {
+                cell: RcuCell::none(),
+                scope: PhantomData,
+            }
+        }
+    }
+
+    /// create scoped rcu cell from value that can be converted to Option<T>
+    #[inline]
+    pub fn new(data: impl Into<Option<T>>) -> Self {
+        ScopedRcuCell {
+            cell: RcuCell::new(data),
+            scope: PhantomData,
+        }
+    }
+
+    /// consume the cell and return the inner value
+    ///
+    /// all the `ScopedRef` guards borrow the cell, so the value is unique
+    /// here, unless a guard was leaked with `mem::forget`, then the value is
+    /// leaked with it and `None` is returned
+    #[inline]
+    pub fn into_inner(self) -> Option<T> {
+        self.cell.into_arc().and_then(Arc::into_inner)
+    }
+
+    /// check if the cell is empty
+    #[inline]
+    pub fn is_none(&self) -> bool {
+        self.cell.is_none()
+    }
+
+    /// read out a snapshot of the inner value
+    #[inline]
+    pub fn read(&self) -> Option<ScopedRef<'_, T>> {
+        self.cell.read().map(|arc| ScopedRef {
+            arc,
+            cell: PhantomData,
+        })
+    }
+
+    /// write a value to the cell, the old value is dropped once
+    /// all of its snapshots are released
+    #[inline]
+    pub fn write(&self, data: T) {
+        self.cell.write(data);
+    }
+
+    /// take the value from the cell, leave the cell empty
+    #[inline]
+    pub fn take(&self) {
+        self.cell.take();
+    }
+
+    /// Atomicly update the value with a closure.
+    /// The closure will be called with a reference to the old value and
+    /// return the new value.
+    #[inline]
+    pub fn update<F>(&self, f: F)
+    where
+        F: FnOnce(Option<&T>) -> Option<T>,
+    {
+        self.cell.update(|old| f(old.as_deref()));
+    }
+}
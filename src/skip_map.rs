@@ -0,0 +1,369 @@
+//! An ordered concurrent map with lock-free readers, built on `RcuCell`s.
+//!
+//! It's a skip list whose links are rcu cells. A reader walks the towers
+//! of the nodes without any lock, every link it reads is an `Arc` of the
+//! next node, so a node removed under it is still valid and still leads
+//! to the rest of the list. The writers are serialized by a writer lock,
+//! a new node is fully built before it's linked bottom-up, and a removed
+//! node is unlinked top-down, then dropped once the last reader leaves it.
+//!
+//! ```
+//! use rcu_cell::skip_map::RcuSkipMap;
+//!
+//! let map = RcuSkipMap::new();
+//! assert!(map.insert(3, "c").is_none());
+//! map.insert(1, "a");
+//! map.insert(2, "b");
+//! assert_eq!(map.get(&2).as_deref(), Some(&"b"));
+//! assert_eq!(map.insert(2, "B").as_deref(), Some(&"b"));
+//! let keys: Vec<_> = map.range(2..).map(|e| *e.key()).collect();
+//! assert_eq!(keys, [2, 3]);
+//! assert_eq!(map.remove(&1).as_deref(), Some(&"a"));
+//! assert_eq!(map.len(), 2);
+//! ```
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::marker::PhantomData;
+use core::ops::{Bound, RangeBounds};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+use crate::park;
+use crate::{RcuCell, RcuCellNonEmpty};
+
+// enough levels for billions of entries with the 1/4 promotion rate
+const MAX_LEVEL: usize = 16;
+
+type Link<K, V> = RcuCell<Node<K, V>>;
+
+struct Node<K, V> {
+    key: K,
+    value: RcuCellNonEmpty<V>,
+    // the links of the levels the node is on, the bottom one first
+    next: Box<[Link<K, V>]>,
+}
+
+impl<K, V> Drop for Node<K, V> {
+    // unlink the nodes one by one, a recursive drop of a long
+    // list would overflow the thread stack
+    fn drop(&mut self) {
+        let mut nodes: Vec<_> = self.next.iter().filter_map(RcuCell::take).collect();
+        while let Some(node) = nodes.pop() {
+            if let Ok(node) = Arc::try_unwrap(node) {
+                nodes.extend(node.next.iter().filter_map(RcuCell::take));
+            }
+        }
+    }
+}
+
+// release the writer lock even if the writer panics, or the other
+// writers would wait for it forever
+struct Unlock<'a, K, V>(&'a RcuSkipMap<K, V>);
+
+impl<K, V> Drop for Unlock<'_, K, V> {
+    fn drop(&mut self) {
+        self.0.locked.store(false, Ordering::Release);
+        park::wake(self.0.key());
+    }
+}
+
+/// An ordered concurrent map whose skip list links are rcu cells
+pub struct RcuSkipMap<K, V> {
+    head: Box<[Link<K, V>]>,
+    len: AtomicUsize,
+    // serialize the writers, the readers never take it
+    locked: AtomicBool,
+    // the state of the random levels, only touched by the lock holder
+    seed: AtomicU32,
+}
+
+impl<K, V> core::fmt::Debug for RcuSkipMap<K, V>
+where
+    K: core::fmt::Debug,
+    V: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let mut map = f.debug_map();
+        for entry in self.iter() {
+            map.entry(entry.key(), entry.value());
+        }
+        map.finish()
+    }
+}
+
+impl<K, V> Default for RcuSkipMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> RcuSkipMap<K, V> {
+    /// create an empty map
+    pub fn new() -> Self {
+        RcuSkipMap {
+            head: (0..MAX_LEVEL).map(|_| RcuCell::none()).collect(),
+            len: AtomicUsize::new(0),
+            locked: AtomicBool::new(false),
+            seed: AtomicU32::new(0x9e37_79b9),
+        }
+    }
+
+    /// the number of entries, it's racy with the concurrent writers
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// check if there is no entry, it's racy with the concurrent writers
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head[0].is_none()
+    }
+
+    /// Iterate over the entries in the key order, the entries inserted or
+    /// removed ahead of the iterator during the iteration may be observed.
+    #[inline]
+    pub fn iter(&self) -> Iter<K, V> {
+        Iter {
+            next: self.head[0].read(),
+        }
+    }
+
+    // the links of a node, the head of the list for `None`
+    #[inline]
+    fn links<'a>(&'a self, node: Option<&'a Node<K, V>>) -> &'a [Link<K, V>] {
+        node.map_or(&self.head, |node| &node.next)
+    }
+
+    // the last node of every level whose key is `before` the target,
+    // `None` stands for the head
+    fn preds<F>(&self, before: F) -> [Option<Arc<Node<K, V>>>; MAX_LEVEL]
+    where
+        F: Fn(&K) -> bool,
+    {
+        let mut preds: [Option<Arc<Node<K, V>>>; MAX_LEVEL] = Default::default();
+        let mut pred = None;
+        for level in (0..MAX_LEVEL).rev() {
+            while let Some(next) = self.links(pred.as_deref())[level].read() {
+                if !before(&next.key) {
+                    break;
+                }
+                pred = Some(next);
+            }
+            preds[level].clone_from(&pred);
+        }
+        preds
+    }
+
+    // the first node whose key is not `before` the target
+    fn seek<F>(&self, before: F) -> Option<Arc<Node<K, V>>>
+    where
+        F: Fn(&K) -> bool,
+    {
+        let mut pred: Option<Arc<Node<K, V>>> = None;
+        for level in (0..MAX_LEVEL).rev() {
+            loop {
+                let next = self.links(pred.as_deref())[level].read();
+                match next {
+                    Some(next) if before(&next.key) => pred = Some(next),
+                    next if level == 0 => return next,
+                    _ => break,
+                }
+            }
+        }
+        None
+    }
+
+    // wait for the other writers, the lock is released when the returned
+    // guard is dropped
+    fn lock(&self) -> Unlock<'_, K, V> {
+        while self.locked.swap(true, Ordering::Acquire) {
+            park::wait_until(self.key(), || !self.locked.load(Ordering::Relaxed));
+        }
+        Unlock(self)
+    }
+
+    #[inline]
+    fn key(&self) -> usize {
+        &self.locked as *const _ as usize
+    }
+
+    // a level promoted with the chance of 1/4, only called under the lock
+    fn random_level(&self) -> usize {
+        // xorshift32, never zero
+        let mut x = self.seed.load(Ordering::Relaxed);
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.seed.store(x, Ordering::Relaxed);
+        (x.trailing_zeros() as usize / 2 + 1).min(MAX_LEVEL)
+    }
+}
+
+impl<K: Ord, V> RcuSkipMap<K, V> {
+    /// get the value of the key
+    pub fn get<Q>(&self, key: &Q) -> Option<Arc<V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let node = self.seek(|k| k.borrow() < key)?;
+        (node.key.borrow() == key).then(|| node.value.read())
+    }
+
+    /// check if the key is in the map
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// insert the value of the key and return the old one
+    pub fn insert(&self, key: K, value: impl Into<Arc<V>>) -> Option<Arc<V>> {
+        let value = value.into();
+        let _unlock = self.lock();
+        let preds = self.preds(|k| *k < key);
+        let next = self.links(preds[0].as_deref())[0].read();
+        if let Some(node) = next.filter(|node| node.key == key) {
+            return Some(node.value.write(value));
+        }
+
+        let height = self.random_level();
+        let next = preds[..height]
+            .iter()
+            .enumerate()
+            .map(|(level, pred)| RcuCell::from(self.links(pred.as_deref())[level].read()))
+            .collect();
+        let node = Arc::new(Node {
+            key,
+            value: RcuCellNonEmpty::from(value),
+            next,
+        });
+        // the node is complete before it's reachable, link it bottom-up
+        for (level, pred) in preds[..height].iter().enumerate() {
+            self.links(pred.as_deref())[level].write(node.clone());
+        }
+        self.len.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    /// remove the key and return its value
+    pub fn remove<Q>(&self, key: &Q) -> Option<Arc<V>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let _unlock = self.lock();
+        let preds = self.preds(|k| k.borrow() < key);
+        let node = self.links(preds[0].as_deref())[0].read()?;
+        if node.key.borrow() != key {
+            return None;
+        }
+        // the readers on the node still walk on through its own links
+        for (level, pred) in preds[..node.next.len()].iter().enumerate().rev() {
+            self.links(pred.as_deref())[level].set(node.next[level].read());
+        }
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        Some(node.value.read())
+    }
+
+    /// Iterate over the entries whose keys are in the `range` in the key
+    /// order, see `iter`
+    pub fn range<Q, R>(&self, range: R) -> Range<K, V, Q, R>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let next = match range.start_bound() {
+            Bound::Included(start) => self.seek(|k| k.borrow() < start),
+            Bound::Excluded(start) => self.seek(|k| k.borrow() <= start),
+            Bound::Unbounded => self.head[0].read(),
+        };
+        Range {
+            iter: Iter { next },
+            range,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// An entry of `RcuSkipMap` yielded by its iterators
+pub struct Entry<K, V> {
+    node: Arc<Node<K, V>>,
+    value: Arc<V>,
+}
+
+impl<K, V> Entry<K, V> {
+    /// the key of the entry
+    #[inline]
+    pub fn key(&self) -> &K {
+        &self.node.key
+    }
+
+    /// the value of the entry when it was visited
+    #[inline]
+    pub fn value(&self) -> &Arc<V> {
+        &self.value
+    }
+}
+
+impl<K: core::fmt::Debug, V: core::fmt::Debug> core::fmt::Debug for Entry<K, V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_tuple("Entry")
+            .field(self.key())
+            .field(self.value())
+            .finish()
+    }
+}
+
+/// The iterator of `RcuSkipMap::iter`
+pub struct Iter<K, V> {
+    next: Option<Arc<Node<K, V>>>,
+}
+
+impl<K, V> Iterator for Iter<K, V> {
+    type Item = Entry<K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next.take()?;
+        self.next = node.next[0].read();
+        let value = node.value.read();
+        Some(Entry { node, value })
+    }
+}
+
+/// The iterator of `RcuSkipMap::range`
+pub struct Range<K, V, Q: ?Sized, R> {
+    iter: Iter<K, V>,
+    range: R,
+    _marker: PhantomData<fn(&Q)>,
+}
+
+impl<K, V, Q, R> Iterator for Range<K, V, Q, R>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    type Item = Entry<K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = self.iter.next.as_ref()?.key.borrow();
+        let in_range = match self.range.end_bound() {
+            Bound::Included(end) => key <= end,
+            Bound::Excluded(end) => key < end,
+            Bound::Unbounded => true,
+        };
+        if !in_range {
+            self.iter.next = None;
+            return None;
+        }
+        self.iter.next()
+    }
+}
@@ -0,0 +1,176 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{RcuCell, RcuStack};
+
+/// The key of a value in `RcuSlab`
+///
+/// It's the index of the slot together with the generation of the value,
+/// so a key of a removed value never matches the value inserted later
+/// into the same slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SlabKey {
+    index: usize,
+    gen: u64,
+}
+
+impl SlabKey {
+    /// the index of the slot, it's stable for the lifetime of the value
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+struct Entry<T> {
+    gen: u64,
+    value: Arc<T>,
+}
+
+struct Slot<T> {
+    entry: RcuCell<Entry<T>>,
+}
+
+/// A growable slab of rcu cells keyed by stable indices
+///
+/// The reads look up the slot and read its value without any lock. The
+/// slots are never moved or shrunk, growing the slab publishes a copy of
+/// the slot table that shares the existing slots, and the indices of the
+/// removed values are reused by the later inserts.
+pub struct RcuSlab<T> {
+    slots: RcuCell<Vec<Arc<Slot<T>>>>,
+    // the keys of the removed values, their slots are free
+    free: RcuStack<SlabKey>,
+    len: AtomicUsize,
+}
+
+impl<T> core::fmt::Debug for RcuSlab<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("RcuSlab")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl<T> Default for RcuSlab<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> RcuSlab<T> {
+    /// create an empty slab
+    #[inline]
+    pub fn new() -> Self {
+        RcuSlab {
+            slots: RcuCell::none(),
+            free: RcuStack::new(),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// the number of values, it's racy with the concurrent writers
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// check if there is no value, it's racy with the concurrent writers
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// the number of slots
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.slots.with_ref(|s| s.map_or(0, Vec::len))
+    }
+
+    // get the slot at `index`, the writers hold it instead of the
+    // slot table so they never stall a concurrent grow
+    #[inline]
+    fn slot(&self, index: usize) -> Option<Arc<Slot<T>>> {
+        self.slots.with_ref(|s| s?.get(index).cloned())
+    }
+
+    // add a new slot and return its index
+    fn grow(&self) -> usize {
+        let mut index = 0;
+        self.slots.update(|old| {
+            let mut new = old.as_deref().cloned().unwrap_or_default();
+            index = new.len();
+            new.push(Arc::new(Slot {
+                entry: RcuCell::none(),
+            }));
+            Some(new)
+        });
+        index
+    }
+
+    /// insert a value into a free slot and return its key
+    pub fn insert(&self, value: impl Into<Arc<T>>) -> SlabKey {
+        let value = value.into();
+        // the slot of a popped key is owned until the value is written
+        let key = match self.free.pop() {
+            Some(old) => SlabKey {
+                index: old.index,
+                gen: old.gen + 1,
+            },
+            None => SlabKey {
+                index: self.grow(),
+                gen: 0,
+            },
+        };
+        let slot = self.slot(key.index).expect("slab slots are never removed");
+        slot.entry.write(Entry {
+            gen: key.gen,
+            value,
+        });
+        self.len.fetch_add(1, Ordering::Relaxed);
+        key
+    }
+
+    /// get the value of the key
+    pub fn get(&self, key: SlabKey) -> Option<Arc<T>> {
+        let entry = self.slots.with_ref(|s| s?.get(key.index)?.entry.read())?;
+        (entry.gen == key.gen).then(|| entry.value.clone())
+    }
+
+    /// check if the key is in the slab
+    #[inline]
+    pub fn contains_key(&self, key: SlabKey) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// replace the value of the key and return the old one,
+    /// return `None` and drop `value` if the key is not in the slab
+    pub fn replace(&self, key: SlabKey, value: impl Into<Arc<T>>) -> Option<Arc<T>> {
+        let value = value.into();
+        let slot = self.slot(key.index)?;
+        let old = slot.entry.update_if(|old| match old {
+            Some(e) if e.gen == key.gen => Ok(Some(Arc::new(Entry {
+                gen: key.gen,
+                value,
+            }))),
+            _ => Err(()),
+        });
+        old.ok()?.map(|e| e.value.clone())
+    }
+
+    /// remove the value of the key and return it,
+    /// the slot is reused by a later insert
+    pub fn remove(&self, key: SlabKey) -> Option<Arc<T>> {
+        let slot = self.slot(key.index)?;
+        let old = slot.entry.update_if(|old| match old {
+            Some(e) if e.gen == key.gen => Ok(None),
+            _ => Err(()),
+        });
+        let old = old.ok()??;
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        self.free.push(key);
+        Some(old.value.clone())
+    }
+}
@@ -0,0 +1,158 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::RcuUnsized;
+
+/// RCU cell that holds an `Arc<str>`, like a hot reloaded token or banner
+///
+/// It always holds a string, an empty one by default.
+pub struct RcuStr {
+    cell: RcuUnsized<str>,
+}
+
+impl fmt::Debug for RcuStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.load_ref(|s| f.debug_tuple("RcuStr").field(&s).finish())
+    }
+}
+
+impl fmt::Display for RcuStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.load_ref(|s| f.write_str(s))
+    }
+}
+
+impl Default for RcuStr {
+    fn default() -> Self {
+        RcuStr::new("")
+    }
+}
+
+impl From<&str> for RcuStr {
+    fn from(s: &str) -> Self {
+        RcuStr::new(s)
+    }
+}
+
+impl From<String> for RcuStr {
+    fn from(s: String) -> Self {
+        RcuStr::new(s)
+    }
+}
+
+impl From<Arc<str>> for RcuStr {
+    fn from(s: Arc<str>) -> Self {
+        RcuStr::new(s)
+    }
+}
+
+impl RcuStr {
+    /// create the rcu cell from a string
+    #[inline]
+    pub fn new(s: impl Into<Arc<str>>) -> Self {
+        RcuStr {
+            cell: RcuUnsized::new(s),
+        }
+    }
+
+    /// read out the string
+    #[inline]
+    pub fn read(&self) -> Arc<str> {
+        self.cell.read().expect("rcu str is never empty")
+    }
+
+    /// Call `f` with the string without touching its refcount, the writers
+    /// of a 32-bit platform wait for it, so it should be short.
+    #[inline]
+    pub fn load_ref<R>(&self, f: impl FnOnce(&str) -> R) -> R {
+        self.cell
+            .with_ref(|s| f(s.expect("rcu str is never empty")))
+    }
+
+    /// write a string and return the old one
+    #[inline]
+    pub fn write(&self, s: impl Into<Arc<str>>) -> Arc<str> {
+        self.cell.write(s).expect("rcu str is never empty")
+    }
+
+    /// the version of the rcu cell, see `RcuCell::version`
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.cell.version()
+    }
+}
+
+/// RCU cell that holds an `Arc<[u8]>`, like a hot reloaded payload
+///
+/// It always holds a byte slice, an empty one by default.
+pub struct RcuBytes {
+    cell: RcuUnsized<[u8]>,
+}
+
+impl fmt::Debug for RcuBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.load_ref(|b| f.debug_tuple("RcuBytes").field(&b).finish())
+    }
+}
+
+impl Default for RcuBytes {
+    fn default() -> Self {
+        RcuBytes::new(&[][..])
+    }
+}
+
+impl From<&[u8]> for RcuBytes {
+    fn from(b: &[u8]) -> Self {
+        RcuBytes::new(b)
+    }
+}
+
+impl From<Vec<u8>> for RcuBytes {
+    fn from(b: Vec<u8>) -> Self {
+        RcuBytes::new(b)
+    }
+}
+
+impl From<Arc<[u8]>> for RcuBytes {
+    fn from(b: Arc<[u8]>) -> Self {
+        RcuBytes::new(b)
+    }
+}
+
+impl RcuBytes {
+    /// create the rcu cell from a byte slice
+    #[inline]
+    pub fn new(b: impl Into<Arc<[u8]>>) -> Self {
+        RcuBytes {
+            cell: RcuUnsized::new(b),
+        }
+    }
+
+    /// read out the byte slice
+    #[inline]
+    pub fn read(&self) -> Arc<[u8]> {
+        self.cell.read().expect("rcu bytes is never empty")
+    }
+
+    /// Call `f` with the byte slice without touching its refcount, see
+    /// `RcuStr::load_ref`
+    #[inline]
+    pub fn load_ref<R>(&self, f: impl FnOnce(&[u8]) -> R) -> R {
+        self.cell
+            .with_ref(|b| f(b.expect("rcu bytes is never empty")))
+    }
+
+    /// write a byte slice and return the old one
+    #[inline]
+    pub fn write(&self, b: impl Into<Arc<[u8]>>) -> Arc<[u8]> {
+        self.cell.write(b).expect("rcu bytes is never empty")
+    }
+
+    /// the version of the rcu cell, see `RcuCell::version`
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.cell.version()
+    }
+}
@@ -0,0 +1,80 @@
+use alloc::sync::Arc;
+
+use crate::RcuCell;
+
+struct Node<T> {
+    value: Arc<T>,
+    next: Option<Arc<Node<T>>>,
+}
+
+impl<T> Drop for Node<T> {
+    // unlink the nodes one by one, a recursive drop of a long
+    // stack would overflow the thread stack
+    fn drop(&mut self) {
+        let mut next = self.next.take();
+        while let Some(node) = next {
+            next = match Arc::try_unwrap(node) {
+                Ok(mut node) => node.next.take(),
+                Err(_) => break,
+            };
+        }
+    }
+}
+
+/// A LIFO stack whose head is an rcu cell
+///
+/// The nodes are immutable and reference counted, `push` and `pop` replace
+/// the head under the update lock of the cell, so a popped node is never
+/// reused while a reader still holds it and there is no ABA problem.
+/// `peek` reads the top value without any lock.
+pub struct RcuStack<T> {
+    head: RcuCell<Node<T>>,
+}
+
+impl<T> core::fmt::Debug for RcuStack<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("RcuStack")
+            .field("head", &self.head.debug_state())
+            .finish()
+    }
+}
+
+impl<T> Default for RcuStack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> RcuStack<T> {
+    /// create an empty stack
+    #[inline]
+    pub fn new() -> Self {
+        RcuStack {
+            head: RcuCell::none(),
+        }
+    }
+
+    /// check if the stack is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// push a value to the top of the stack
+    pub fn push(&self, value: impl Into<Arc<T>>) {
+        let value = value.into();
+        self.head.update(|next| Some(Node { value, next }));
+    }
+
+    /// pop the value on the top of the stack
+    pub fn pop(&self) -> Option<Arc<T>> {
+        let old = self.head.update(|top| top?.next.clone());
+        old.map(|node| node.value.clone())
+    }
+
+    /// read the value on the top of the stack without popping it
+    #[inline]
+    pub fn peek(&self) -> Option<Arc<T>> {
+        self.head.read().map(|node| node.value.clone())
+    }
+}
@@ -0,0 +1,98 @@
+// The atomics of the link.
+//
+// They are the atomics of `core`, or the ones of `portable-atomic` with the
+// `portable-atomic` feature. When the crate is built with `--cfg loom` they
+// are the atomics of loom, so the interleavings of the readers and the
+// writers of the link can be model checked, see `tests/loom.rs`.
+
+#[cfg(all(not(loom), not(feature = "portable-atomic")))]
+use core::sync::atomic;
+#[cfg(all(not(loom), feature = "portable-atomic"))]
+use portable_atomic as atomic;
+
+// not every layout needs all of them
+#[cfg(not(loom))]
+#[allow(unused_imports)]
+pub(crate) use atomic::{fence, AtomicPtr, AtomicUsize};
+#[cfg(loom)]
+#[allow(unused_imports)]
+pub(crate) use loom::sync::atomic::{fence, AtomicUsize};
+
+// only the packed layout needs it
+#[cfg(all(target_pointer_width = "64", not(feature = "two-word"), not(loom)))]
+pub(crate) use atomic::AtomicU64;
+#[cfg(all(target_pointer_width = "64", not(feature = "two-word"), loom))]
+pub(crate) use loom::sync::atomic::AtomicU64;
+
+#[cfg(loom)]
+use core::sync::atomic::Ordering;
+
+// the atomics of loom can't be created in a const context, so the fns that
+// create a link are only const without `--cfg loom`
+macro_rules! const_fn {
+    ($(#[$attr:meta])* $vis:vis const fn $($rest:tt)*) => {
+        #[cfg(not(loom))]
+        $(#[$attr])* $vis const fn $($rest)*
+        #[cfg(loom)]
+        $(#[$attr])* $vis fn $($rest)*
+    };
+}
+pub(crate) use const_fn;
+
+// the `AtomicPtr` of loom, with the bitwise operations on the address that
+// the packed link needs
+#[cfg(loom)]
+pub(crate) struct AtomicPtr<T>(loom::sync::atomic::AtomicPtr<T>);
+
+#[cfg(loom)]
+#[allow(dead_code)]
+impl<T> AtomicPtr<T> {
+    pub(crate) fn new(ptr: *mut T) -> Self {
+        AtomicPtr(loom::sync::atomic::AtomicPtr::new(ptr))
+    }
+
+    pub(crate) fn load(&self, order: Ordering) -> *mut T {
+        self.0.load(order)
+    }
+
+    pub(crate) fn swap(&self, ptr: *mut T, order: Ordering) -> *mut T {
+        self.0.swap(ptr, order)
+    }
+
+    pub(crate) fn compare_exchange_weak(
+        &self,
+        current: *mut T,
+        new: *mut T,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<*mut T, *mut T> {
+        self.0.compare_exchange_weak(current, new, success, failure)
+    }
+
+    pub(crate) fn fetch_and(&self, mask: usize, order: Ordering) -> *mut T {
+        self.fetch_map_addr(order, |addr| addr & mask)
+    }
+
+    pub(crate) fn fetch_or(&self, mask: usize, order: Ordering) -> *mut T {
+        self.fetch_map_addr(order, |addr| addr | mask)
+    }
+
+    // loom has no `get_mut`
+    pub(crate) fn with_mut<R>(&mut self, f: impl FnOnce(&mut *mut T) -> R) -> R {
+        self.0.with_mut(f)
+    }
+
+    fn fetch_map_addr(&self, order: Ordering, f: impl Fn(usize) -> usize) -> *mut T {
+        let failure = match order {
+            Ordering::AcqRel => Ordering::Acquire,
+            Ordering::Release => Ordering::Relaxed,
+            order => order,
+        };
+        match self
+            .0
+            .fetch_update(order, failure, |ptr| Some(ptr.map_addr(&f)))
+        {
+            Ok(ptr) | Err(ptr) => ptr,
+        }
+    }
+}
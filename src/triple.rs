@@ -0,0 +1,165 @@
+//! A triple buffer for a single producer and a single consumer.
+//!
+//! The producer writes into its back buffer and publishes it with a single
+//! atomic swap, the consumer always reads the latest published buffer. No
+//! side ever allocates or waits for the other, a value that is published
+//! over before being read is simply skipped.
+//!
+//! ```
+//! use rcu_cell::triple::TripleBuffer;
+//!
+//! let (mut input, mut output) = TripleBuffer::new(0).split();
+//! input.write(1);
+//! input.write(2);
+//! assert!(output.updated());
+//! assert_eq!(*output.read(), 2);
+//! assert!(!output.updated());
+//! ```
+
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+// set in the middle index when it holds a value not read yet
+const DIRTY: u8 = 0b100;
+const INDEX_MASK: u8 = 0b11;
+
+struct Shared<T> {
+    buffers: [UnsafeCell<T>; 3],
+    // the index of the buffer between the two sides and the dirty bit
+    middle: AtomicU8,
+}
+
+// each side only touches its own buffer, and hands it over with the swap
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+/// A triple buffer, `split` it into the producer and the consumer side
+pub struct TripleBuffer<T> {
+    shared: Shared<T>,
+}
+
+// the buffers are not printed, a `&TripleBuffer` can be shared between
+// threads while `T` is only `Send`
+impl<T> fmt::Debug for TripleBuffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let middle = self.shared.middle.load(Ordering::Relaxed);
+        f.debug_struct("TripleBuffer")
+            .field("middle", &(middle & INDEX_MASK))
+            .field("dirty", &(middle & DIRTY != 0))
+            .finish()
+    }
+}
+
+impl<T: Clone> TripleBuffer<T> {
+    /// create the triple buffer with the initial value
+    pub fn new(init: T) -> Self {
+        Self::from_fn(|| init.clone())
+    }
+}
+
+impl<T: Default> Default for TripleBuffer<T> {
+    fn default() -> Self {
+        Self::from_fn(T::default)
+    }
+}
+
+impl<T> TripleBuffer<T> {
+    /// create the triple buffer with the three initial buffers built by `f`
+    pub fn from_fn(mut f: impl FnMut() -> T) -> Self {
+        TripleBuffer {
+            shared: Shared {
+                buffers: [
+                    UnsafeCell::new(f()),
+                    UnsafeCell::new(f()),
+                    UnsafeCell::new(f()),
+                ],
+                middle: AtomicU8::new(1),
+            },
+        }
+    }
+
+    /// split the triple buffer into the producer and the consumer side
+    pub fn split(self) -> (Input<T>, Output<T>) {
+        let shared = Arc::new(self.shared);
+        let input = Input {
+            shared: shared.clone(),
+            back: 0,
+        };
+        (input, Output { shared, front: 2 })
+    }
+}
+
+/// The producer side of a triple buffer
+pub struct Input<T> {
+    shared: Arc<Shared<T>>,
+    back: u8,
+}
+
+impl<T> fmt::Debug for Input<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Input").field("back", &self.back).finish()
+    }
+}
+
+impl<T> Input<T> {
+    /// the back buffer, it holds a stale value, fill it in place and
+    /// `publish` it
+    #[inline]
+    pub fn input_buffer(&mut self) -> &mut T {
+        unsafe { &mut *self.shared.buffers[self.back as usize].get() }
+    }
+
+    /// Publish the back buffer and take the buffer between the two sides
+    /// as the new back buffer. Return true if the previously published
+    /// value is skipped without being read.
+    pub fn publish(&mut self) -> bool {
+        let old = self.shared.middle.swap(self.back | DIRTY, Ordering::AcqRel);
+        self.back = old & INDEX_MASK;
+        old & DIRTY != 0
+    }
+
+    /// write a value into the back buffer and publish it, see `publish`
+    #[inline]
+    pub fn write(&mut self, value: T) -> bool {
+        *self.input_buffer() = value;
+        self.publish()
+    }
+
+    /// check if the consumer has read the last published value
+    #[inline]
+    pub fn consumed(&self) -> bool {
+        self.shared.middle.load(Ordering::Relaxed) & DIRTY == 0
+    }
+}
+
+/// The consumer side of a triple buffer
+pub struct Output<T> {
+    shared: Arc<Shared<T>>,
+    front: u8,
+}
+
+impl<T> fmt::Debug for Output<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Output")
+            .field("front", &self.front)
+            .finish()
+    }
+}
+
+impl<T> Output<T> {
+    /// check if there is a published value not read yet
+    #[inline]
+    pub fn updated(&self) -> bool {
+        self.shared.middle.load(Ordering::Relaxed) & DIRTY != 0
+    }
+
+    /// read the latest published value
+    pub fn read(&mut self) -> &T {
+        if self.updated() {
+            let old = self.shared.middle.swap(self.front, Ordering::AcqRel);
+            self.front = old & INDEX_MASK;
+        }
+        unsafe { &*self.shared.buffers[self.front as usize].get() }
+    }
+}
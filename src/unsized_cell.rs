@@ -0,0 +1,127 @@
+use alloc::sync::Arc;
+use core::fmt;
+
+use crate::sync::const_fn;
+use crate::RcuCell;
+
+/// RCU cell for the unsized values, like `Arc<dyn Trait>`, `Arc<[T]>` and
+/// `Arc<str>`, it behaves like `RwLock<Option<Arc<T>>>`
+///
+/// The link of `RcuCell` only holds thin pointers, so the fat `Arc<T>` is
+/// boxed into an `RcuCell<Arc<T>>`. A read borrows the boxed `Arc` under
+/// the reader count and only clones the inner one, a write allocates the
+/// box besides the value.
+pub struct RcuUnsized<T: ?Sized> {
+    cell: RcuCell<Arc<T>>,
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RcuUnsized<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RcuUnsized")
+            .field("value", &self.read())
+            .finish()
+    }
+}
+
+impl<T: ?Sized> Default for RcuUnsized<T> {
+    fn default() -> Self {
+        RcuUnsized::none()
+    }
+}
+
+impl<T: ?Sized> From<Arc<T>> for RcuUnsized<T> {
+    fn from(data: Arc<T>) -> Self {
+        RcuUnsized {
+            cell: RcuCell::some(data),
+        }
+    }
+}
+
+impl<T: ?Sized> From<Option<Arc<T>>> for RcuUnsized<T> {
+    fn from(data: Option<Arc<T>>) -> Self {
+        RcuUnsized {
+            cell: RcuCell::new(data),
+        }
+    }
+}
+
+impl<T: ?Sized> RcuUnsized<T> {
+    const_fn! {
+        /// create an empty rcu cell
+        #[inline]
+        pub const fn none() -> Self {
+            RcuUnsized {
+                cell: RcuCell::none(),
+            }
+        }
+    }
+
+    /// create rcu cell from a value that can be converted to `Arc<T>`
+    #[inline]
+    pub fn new(data: impl Into<Arc<T>>) -> Self {
+        Self::from(data.into())
+    }
+
+    /// convert the rcu cell to the inner Arc value
+    #[inline]
+    pub fn into_arc(self) -> Option<Arc<T>> {
+        self.cell.into_arc().map(Arc::unwrap_or_clone)
+    }
+
+    /// check if the rcu cell is empty
+    #[inline]
+    pub fn is_none(&self) -> bool {
+        self.cell.is_none()
+    }
+
+    /// read out the inner Arc value
+    #[inline]
+    pub fn read(&self) -> Option<Arc<T>> {
+        self.cell.with_ref(|v| v.cloned())
+    }
+
+    // call `f` with a reference to the current value under the reader
+    // count, no refcount is touched
+    #[inline]
+    pub(crate) fn with_ref<R>(&self, f: impl FnOnce(Option<&T>) -> R) -> R {
+        self.cell.with_ref(|v| f(v.map(|v| &**v)))
+    }
+
+    /// write an option arc value to the rcu cell and return the old value
+    #[inline]
+    pub fn set(&self, data: Option<Arc<T>>) -> Option<Arc<T>> {
+        let old = self.cell.set(data.map(Arc::new));
+        old.map(Arc::unwrap_or_clone)
+    }
+
+    /// take the value from the rcu cell, leave the rcu cell empty
+    #[inline]
+    pub fn take(&self) -> Option<Arc<T>> {
+        self.set(None)
+    }
+
+    /// write a value to the rcu cell and return the old value
+    #[inline]
+    pub fn write(&self, data: impl Into<Arc<T>>) -> Option<Arc<T>> {
+        self.set(Some(data.into()))
+    }
+
+    /// Atomicly update the value with a closure and return the old value,
+    /// see `RcuCell::update`
+    pub fn update<R, F>(&self, f: F) -> Option<Arc<T>>
+    where
+        F: FnOnce(Option<Arc<T>>) -> Option<R>,
+        R: Into<Arc<T>>,
+    {
+        let old = self
+            .cell
+            .update(|old| f(old.as_deref().cloned()).map(|v| Arc::new(v.into())));
+        old.map(Arc::unwrap_or_clone)
+    }
+
+    /// the version of the rcu cell, see `RcuCell::version`
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.cell.version()
+    }
+}
@@ -0,0 +1,152 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
+
+use crate::backoff::Backoff;
+
+struct Unlock<'a>(&'a AtomicUsize, usize);
+
+impl Drop for Unlock<'_> {
+    fn drop(&mut self) {
+        self.0.store(self.1, Ordering::Release);
+    }
+}
+
+/// A cell that stores a small `Copy` value inline under a sequence lock
+///
+/// There is no `Arc` and no allocation, `load` copies the value out and
+/// retries if a `store` happened in the middle of it, the writers are
+/// serialized by the sequence counter. It suits the values of a few words
+/// like the flags, the counters and the small config structs, a large value
+/// makes the readers retry more often.
+pub struct RcuValue<T: Copy> {
+    // odd while a writer is storing
+    seq: AtomicUsize,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: Copy + Send> Send for RcuValue<T> {}
+unsafe impl<T: Copy + Send> Sync for RcuValue<T> {}
+
+// a panic in `update` never leaves a partly stored value behind
+impl<T: Copy> core::panic::UnwindSafe for RcuValue<T> {}
+impl<T: Copy> core::panic::RefUnwindSafe for RcuValue<T> {}
+
+impl<T: Copy + fmt::Debug> fmt::Debug for RcuValue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RcuValue")
+            .field("value", &self.load())
+            .field("version", &self.version())
+            .finish()
+    }
+}
+
+impl<T: Copy + Default> Default for RcuValue<T> {
+    fn default() -> Self {
+        RcuValue::new(T::default())
+    }
+}
+
+impl<T: Copy> From<T> for RcuValue<T> {
+    fn from(value: T) -> Self {
+        RcuValue::new(value)
+    }
+}
+
+impl<T: Copy> RcuValue<T> {
+    /// create the cell from the initial value
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        RcuValue {
+            seq: AtomicUsize::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// convert the cell to the inner value
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    /// get a mutable reference to the value through exclusive access
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// copy out the value
+    pub fn load(&self) -> T {
+        let backoff = Backoff::new();
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq & 1 == 0 {
+                // the copy may be torn by a writer, it's only assumed to be
+                // a valid value once the sequence is found unchanged
+                let value =
+                    unsafe { ptr::read_volatile(self.value.get().cast::<MaybeUninit<T>>()) };
+                fence(Ordering::Acquire);
+                if self.seq.load(Ordering::Relaxed) == seq {
+                    return unsafe { value.assume_init() };
+                }
+            }
+            backoff.snooze();
+        }
+    }
+
+    /// store a value and return the old one
+    #[inline]
+    pub fn swap(&self, value: T) -> T {
+        self.update(|_| value)
+    }
+
+    /// store a value
+    #[inline]
+    pub fn store(&self, value: T) {
+        self.swap(value);
+    }
+
+    /// Atomicly update the value with a closure and return the old value,
+    /// the readers retry until it returns, so it should be short.
+    pub fn update<F>(&self, f: F) -> T
+    where
+        F: FnOnce(T) -> T,
+    {
+        let seq = self.lock();
+        // the writers are locked out, the value is never torn here
+        let old = unsafe { ptr::read(self.value.get()) };
+        // release the lock untouched if `f` panics
+        let unlock = Unlock(&self.seq, seq);
+        let new = f(old);
+        core::mem::forget(unlock);
+        unsafe { ptr::write_volatile(self.value.get(), new) };
+        self.seq.store(seq + 2, Ordering::Release);
+        old
+    }
+
+    /// the version of the cell, it increases on every store
+    #[inline]
+    pub fn version(&self) -> u64 {
+        (self.seq.load(Ordering::Acquire) >> 1) as u64
+    }
+
+    // wait for the other writers and mark the value as being stored
+    fn lock(&self) -> usize {
+        let backoff = Backoff::new();
+        loop {
+            let seq = self.seq.load(Ordering::Relaxed);
+            if seq & 1 == 0
+                && self
+                    .seq
+                    .compare_exchange_weak(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                fence(Ordering::Release);
+                return seq;
+            }
+            backoff.snooze();
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::RcuCell;
+
+type Snapshot<T> = Arc<[Arc<T>]>;
+
+/// A vector that publishes an immutable snapshot after every change
+///
+/// The readers `load` the current snapshot without any lock, a writer
+/// copies the snapshot with its change and publishes the copy, the copy
+/// only clones the `Arc`s of the elements so they are shared by all the
+/// snapshots. The writers wait for each other, so it suits the vectors
+/// that are read a lot more than they are changed, like the listener lists.
+pub struct RcuVec<T> {
+    cell: RcuCell<Snapshot<T>>,
+}
+
+impl<T: core::fmt::Debug> core::fmt::Debug for RcuVec<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_list().entries(self.load().iter()).finish()
+    }
+}
+
+impl<T> Default for RcuVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> FromIterator<T> for RcuVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::from_snapshot(iter.into_iter().map(Arc::new).collect())
+    }
+}
+
+impl<T> From<Vec<T>> for RcuVec<T> {
+    fn from(v: Vec<T>) -> Self {
+        v.into_iter().collect()
+    }
+}
+
+impl<T> RcuVec<T> {
+    /// create an empty vector
+    #[inline]
+    pub fn new() -> Self {
+        Self::from_snapshot(Arc::new([]))
+    }
+
+    #[inline]
+    fn from_snapshot(snapshot: Snapshot<T>) -> Self {
+        RcuVec {
+            cell: RcuCell::new(snapshot),
+        }
+    }
+
+    /// load the current snapshot, it's never changed by the writers
+    #[inline]
+    pub fn load(&self) -> Arc<[Arc<T>]> {
+        Snapshot::clone(&self.cell.read().expect("rcu vec is never unset"))
+    }
+
+    /// the number of elements in the current snapshot
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.cell.with_ref(|v| v.map_or(0, |v| v.len()))
+    }
+
+    /// check if the current snapshot has no element
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// get the element at `idx` in the current snapshot
+    #[inline]
+    pub fn get(&self, idx: usize) -> Option<Arc<T>> {
+        self.cell.with_ref(|v| v?.get(idx).cloned())
+    }
+
+    // publish the snapshot built by `f` from the current one,
+    // nothing is published if `f` returns `None`
+    fn modify<R, F>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&[Arc<T>]) -> Option<(Snapshot<T>, R)>,
+    {
+        let mut ret = None;
+        let _ = self.cell.update_if(|old| {
+            let old = old.expect("rcu vec is never unset");
+            let Some((new, r)) = f(old) else {
+                return Err(());
+            };
+            ret = Some(r);
+            Ok(Some(Arc::new(new)))
+        });
+        ret
+    }
+
+    /// append an element to the back
+    pub fn push(&self, value: impl Into<Arc<T>>) {
+        let value = value.into();
+        self.modify(|old| {
+            let new = old.iter().cloned().chain(Some(value)).collect();
+            Some((new, ()))
+        });
+    }
+
+    /// remove the element at `idx` and shift the later ones,
+    /// return `None` if `idx` is out of bounds
+    pub fn remove(&self, idx: usize) -> Option<Arc<T>> {
+        self.modify(|old| {
+            let removed = old.get(idx)?.clone();
+            let new = old[..idx].iter().chain(&old[idx + 1..]).cloned().collect();
+            Some((new, removed))
+        })
+    }
+
+    /// replace the element at `idx` and return the old one,
+    /// return `None` and leave the vector untouched if `idx` is out of bounds
+    pub fn set(&self, idx: usize, value: impl Into<Arc<T>>) -> Option<Arc<T>> {
+        let value = value.into();
+        self.modify(|old| {
+            let replaced = old.get(idx)?.clone();
+            let mut new = old.to_vec();
+            new[idx] = value;
+            Some((new.into(), replaced))
+        })
+    }
+
+    /// keep only the elements that `f` returns `true` for,
+    /// nothing is published if all of them are kept
+    pub fn retain<F>(&self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.modify(|old| {
+            let new: Vec<_> = old.iter().filter(|v| f(v)).cloned().collect();
+            (new.len() != old.len()).then(|| (new.into(), ()))
+        });
+    }
+
+    /// remove all the elements
+    pub fn clear(&self) {
+        self.modify(|old| (!old.is_empty()).then(|| (Arc::from([]), ())));
+    }
+}
@@ -0,0 +1,239 @@
+//! A single-producer, multi-consumer channel that only keeps the latest
+//! value, built on an `RcuCell`.
+//!
+//! The sender publishes into the cell, the receivers `borrow` the latest
+//! value without any lock and track the version they have seen. Waiting
+//! for a change blocks the thread with `wait_changed`, or the task with
+//! `changed().await` with the `async` feature.
+//!
+//! ```
+//! use rcu_cell::watch;
+//!
+//! let (tx, mut rx) = watch::channel("v1");
+//! assert_eq!(*rx.borrow(), "v1");
+//! assert!(!rx.has_changed().unwrap());
+//! tx.send("v2").unwrap();
+//! assert!(rx.has_changed().unwrap());
+//! assert_eq!(*rx.borrow_and_update(), "v2");
+//! drop(tx);
+//! assert!(rx.wait_changed().is_err());
+//! ```
+
+use alloc::sync::Arc;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::{park, RcuCell};
+
+/// The error of the receivers when the sender is dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the sender is dropped")
+    }
+}
+
+/// The error of `Sender::send` when all the receivers are dropped,
+/// it gives back the value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("all the receivers are dropped")
+    }
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    cell: RcuCell<T>,
+    receivers: AtomicUsize,
+    closed: AtomicBool,
+}
+
+impl<T> Shared<T> {
+    #[inline]
+    fn borrow(&self) -> Arc<T> {
+        self.cell.read().expect("watch value is never empty")
+    }
+
+    // check if there is a value newer than `version`, a newer value is
+    // still reported after the sender is dropped
+    #[inline]
+    fn check(&self, version: u64) -> Option<Result<(), RecvError>> {
+        if self.cell.version() != version {
+            Some(Ok(()))
+        } else if self.closed.load(Ordering::Acquire) {
+            Some(Err(RecvError))
+        } else {
+            None
+        }
+    }
+}
+
+/// Create a watch channel with the initial value
+pub fn channel<T>(init: impl Into<Arc<T>>) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        cell: RcuCell::from(init.into()),
+        receivers: AtomicUsize::new(1),
+        closed: AtomicBool::new(false),
+    });
+    let rx = Receiver {
+        version: shared.cell.version(),
+        shared: shared.clone(),
+    };
+    (Sender { shared }, rx)
+}
+
+/// The sending half of a watch channel
+#[derive(Debug)]
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Publish a new value, fail if all the receivers are dropped
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        if self.receiver_count() == 0 {
+            return Err(SendError(value));
+        }
+        self.shared.cell.write(value);
+        Ok(())
+    }
+
+    /// publish a new value even if there is no receiver,
+    /// and return the old value
+    pub fn send_replace(&self, value: impl Into<Arc<T>>) -> Arc<T> {
+        let old = self.shared.cell.write(value);
+        old.expect("watch value is never empty")
+    }
+
+    /// get the latest value
+    #[inline]
+    pub fn borrow(&self) -> Arc<T> {
+        self.shared.borrow()
+    }
+
+    /// create a new receiver that has seen the latest value
+    pub fn subscribe(&self) -> Receiver<T> {
+        self.shared.receivers.fetch_add(1, Ordering::Relaxed);
+        Receiver {
+            version: self.shared.cell.version(),
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// the number of receivers
+    #[inline]
+    pub fn receiver_count(&self) -> usize {
+        self.shared.receivers.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        let key = self.shared.cell.key();
+        park::wake(key);
+        #[cfg(feature = "async")]
+        crate::notify::wake(key);
+    }
+}
+
+/// The receiving half of a watch channel
+#[derive(Debug)]
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    // the version of the last seen value
+    version: u64,
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.shared.receivers.fetch_add(1, Ordering::Relaxed);
+        Receiver {
+            shared: self.shared.clone(),
+            version: self.version,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.receivers.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl<T> Receiver<T> {
+    /// get the latest value without marking it as seen
+    #[inline]
+    pub fn borrow(&self) -> Arc<T> {
+        self.shared.borrow()
+    }
+
+    /// get the latest value and mark it as seen
+    #[inline]
+    pub fn borrow_and_update(&mut self) -> Arc<T> {
+        // load the version before reading, a value published in between
+        // is reported as a change again
+        self.version = self.shared.cell.version();
+        self.shared.borrow()
+    }
+
+    /// check if there is a value that is not seen yet,
+    /// fail if there is none and the sender is dropped
+    #[inline]
+    pub fn has_changed(&self) -> Result<bool, RecvError> {
+        match self.shared.check(self.version) {
+            Some(ret) => ret.map(|()| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Block the thread until there is a value that is not seen yet and
+    /// mark it as seen, fail if the sender is dropped before that.
+    ///
+    /// Without the `std` feature the thread spins instead.
+    pub fn wait_changed(&mut self) -> Result<(), RecvError> {
+        let shared = &*self.shared;
+        park::wait_until(shared.cell.key(), || shared.check(self.version).is_some());
+        self.mark_seen()
+    }
+
+    /// Wait until there is a value that is not seen yet and mark it as
+    /// seen, fail if the sender is dropped before that.
+    #[cfg(feature = "async")]
+    pub async fn changed(&mut self) -> Result<(), RecvError> {
+        use core::task::Poll;
+
+        let shared = &*self.shared;
+        let version = self.version;
+        let mut waiter = crate::notify::Waiter::new(shared.cell.key());
+        core::future::poll_fn(|cx| {
+            if shared.check(version).is_some() {
+                return Poll::Ready(());
+            }
+            waiter.register(cx.waker());
+            match shared.check(version) {
+                Some(_) => Poll::Ready(()),
+                None => Poll::Pending,
+            }
+        })
+        .await;
+        self.mark_seen()
+    }
+
+    // mark the latest value as seen after waiting,
+    // it's not changed only if the sender is dropped
+    #[inline]
+    fn mark_seen(&mut self) -> Result<(), RecvError> {
+        let version = self.shared.cell.version();
+        if version == self.version {
+            return Err(RecvError);
+        }
+        self.version = version;
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use alloc::sync::{Arc, Weak};
+
+use crate::RcuVec;
+
+/// A list of the weak subscribers that prunes the dead ones by itself
+///
+/// It's the registry of an event broadcaster: the subscribers are pushed as
+/// `Weak`, so the list never keeps them alive, and `for_each_alive` calls
+/// back the alive ones from a snapshot of the list without any lock. The
+/// entries found dead by a traversal are removed after it, a subscriber
+/// only has to be dropped to unsubscribe.
+pub struct RcuWeakList<T> {
+    list: RcuVec<Weak<T>>,
+}
+
+impl<T> core::fmt::Debug for RcuWeakList<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("RcuWeakList")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<T> Default for RcuWeakList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> RcuWeakList<T> {
+    /// create an empty list
+    #[inline]
+    pub fn new() -> Self {
+        RcuWeakList {
+            list: RcuVec::new(),
+        }
+    }
+
+    /// the number of the entries, the dead ones not pruned yet included
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    /// check if the list has no entry
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// append a subscriber to the back of the list
+    #[inline]
+    pub fn push(&self, subscriber: Weak<T>) {
+        self.list.push(subscriber);
+    }
+
+    /// Call `f` with each alive subscriber in the order they are pushed,
+    /// and return the number of them. The dead entries are removed once
+    /// the traversal is done, so `f` never runs under the update lock.
+    pub fn for_each_alive<F>(&self, mut f: F) -> usize
+    where
+        F: FnMut(Arc<T>),
+    {
+        let snapshot = self.list.load();
+        let mut alive = 0;
+        for subscriber in snapshot.iter() {
+            if let Some(v) = subscriber.upgrade() {
+                alive += 1;
+                f(v);
+            }
+        }
+        if alive != snapshot.len() {
+            self.prune();
+        }
+        alive
+    }
+
+    /// remove the dead entries, nothing is published if there is none
+    #[inline]
+    pub fn prune(&self) {
+        self.list.retain(|v| v.strong_count() > 0);
+    }
+
+    /// remove all the entries
+    #[inline]
+    pub fn clear(&self) {
+        self.list.clear();
+    }
+}
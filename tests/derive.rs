@@ -0,0 +1,64 @@
+//! The per-field rcu views generated by `#[derive(RcuFields)]`.
+#![cfg(feature = "derive")]
+
+use rcu_cell::RcuFields;
+
+#[derive(Clone, Debug, PartialEq, RcuFields)]
+pub struct Config {
+    pub port: u16,
+    pub log_level: String,
+    r#type: Vec<u8>,
+}
+
+fn config() -> Config {
+    Config {
+        port: 80,
+        log_level: "info".into(),
+        r#type: vec![1],
+    }
+}
+
+#[test]
+fn snapshot() {
+    let fields = ConfigRcuFields::from(config());
+    assert_eq!(fields.snapshot(), config());
+    assert_eq!(*fields.port.read(), 80);
+    assert_eq!(fields.r#type.read().as_slice(), &[1]);
+}
+
+#[test]
+fn update_field() {
+    let fields = ConfigRcuFields::new(config());
+    let level = fields.log_level.read();
+    fields.update_field(ConfigRcuField::LogLevel("debug".into()));
+    fields.update_field(ConfigRcuField::Type(vec![2]));
+    // the readers keep the old value of the field
+    assert_eq!(level.as_str(), "info");
+    assert_eq!(
+        fields.snapshot(),
+        Config {
+            port: 80,
+            log_level: "debug".into(),
+            r#type: vec![2],
+        }
+    );
+}
+
+#[test]
+fn update_while_read() {
+    let fields = ConfigRcuFields::new(config());
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            for port in 1..=1000 {
+                fields.update_field(ConfigRcuField::Port(port));
+            }
+        });
+        s.spawn(|| {
+            for _ in 0..1000 {
+                // the other fields are never touched by the port updates
+                assert_eq!(fields.snapshot().log_level, "info");
+            }
+        });
+    });
+    assert_eq!(*fields.port.read(), 1000);
+}
@@ -0,0 +1,240 @@
+//! Model check the link protocol with loom, every interleaving of the
+//! readers and the writers is explored and no value may be leaked, freed
+//! twice or read torn.
+//!
+//! ```sh
+//! RUSTFLAGS="--cfg loom" cargo test --release --test loom
+//! RUSTFLAGS="--cfg loom" cargo test --release --test loom --features two-word
+//! ```
+#![cfg(loom)]
+
+use loom::thread;
+use rcu_cell::{RcuCell, RcuWeak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// a value that counts its drops, the count is checked at the end of each
+// explored execution
+struct Tracked {
+    value: usize,
+    drops: Arc<AtomicUsize>,
+}
+
+impl Tracked {
+    fn new(value: usize, drops: &Arc<AtomicUsize>) -> Self {
+        Tracked {
+            value,
+            drops: drops.clone(),
+        }
+    }
+}
+
+impl Clone for Tracked {
+    fn clone(&self) -> Self {
+        Tracked::new(self.value, &self.drops)
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn read_while_write() {
+    loom::model(|| {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let cell = Arc::new(RcuCell::new(Tracked::new(1, &drops)));
+
+        let reader = {
+            let cell = cell.clone();
+            thread::spawn(move || cell.read().map(|v| v.value))
+        };
+        let old = cell.write(Tracked::new(2, &drops));
+        assert_eq!(old.map(|v| v.value), Some(1));
+
+        let value = reader.join().unwrap();
+        assert!(value == Some(1) || value == Some(2));
+        assert_eq!(cell.read().map(|v| v.value), Some(2));
+        drop(cell);
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+    });
+}
+
+#[test]
+fn downgrade_while_write() {
+    loom::model(|| {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let cell = Arc::new(RcuCell::new(Tracked::new(1, &drops)));
+
+        let reader = {
+            let cell = cell.clone();
+            // the laggard reader holds no strong count of its own
+            thread::spawn(move || cell.downgrade())
+        };
+        // the old value is released right away
+        let old = cell.write(Tracked::new(2, &drops)).unwrap();
+        let weak = Arc::downgrade(&old);
+        drop(old);
+
+        let value = reader.join().unwrap();
+        // the old value is released only once all the strong counts are
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+        assert!(weak.upgrade().is_none());
+        assert!(value.upgrade().is_none() || value.upgrade().map(|v| v.value) == Some(2));
+        drop(cell);
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+    });
+}
+
+#[test]
+fn read_while_take() {
+    loom::model(|| {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let cell = Arc::new(RcuCell::new(Tracked::new(1, &drops)));
+
+        let reader = {
+            let cell = cell.clone();
+            thread::spawn(move || {
+                // the value outlives the take
+                let v = cell.read();
+                v.map(|v| v.value)
+            })
+        };
+        drop(cell.take());
+
+        let value = reader.join().unwrap();
+        assert!(value.is_none() || value == Some(1));
+        assert!(cell.is_none());
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+    });
+}
+
+#[test]
+fn concurrent_updates() {
+    loom::model(|| {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let cell = Arc::new(RcuCell::new(Tracked::new(0, &drops)));
+
+        let updater = {
+            let cell = cell.clone();
+            let drops = drops.clone();
+            thread::spawn(move || {
+                cell.update(|v| v.map(|v| Tracked::new(v.value + 1, &drops)));
+            })
+        };
+        cell.update(|v| v.map(|v| Tracked::new(v.value + 1, &drops)));
+        updater.join().unwrap();
+
+        assert_eq!(cell.read().map(|v| v.value), Some(2));
+        drop(cell);
+        assert_eq!(drops.load(Ordering::Relaxed), 3);
+    });
+}
+
+#[test]
+fn update_mut_while_read() {
+    loom::model(|| {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let cell = Arc::new(RcuCell::new(Tracked::new(1, &drops)));
+
+        let reader = {
+            let cell = cell.clone();
+            thread::spawn(move || cell.read().map(|v| v.value))
+        };
+        assert!(cell.update_mut(|v| v.value += 1));
+
+        // a reader never sees the value in the middle of the mutation
+        let value = reader.join().unwrap();
+        assert!(value == Some(1) || value == Some(2));
+        assert_eq!(cell.read().map(|v| v.value), Some(2));
+    });
+}
+
+#[test]
+fn compare_exchange_race() {
+    use std::sync::atomic::Ordering::SeqCst;
+
+    loom::model(|| {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let cell = Arc::new(RcuCell::new(Tracked::new(0, &drops)));
+        let current = cell.read().unwrap();
+
+        let racer = {
+            let cell = cell.clone();
+            let current = current.clone();
+            let new = Arc::new(Tracked::new(1, &drops));
+            thread::spawn(move || unsafe {
+                cell.compare_exchange(Arc::as_ptr(&current), Some(&new), SeqCst, SeqCst)
+                    .is_ok()
+            })
+        };
+        let new = Arc::new(Tracked::new(2, &drops));
+        let won =
+            unsafe { cell.compare_exchange(Arc::as_ptr(&current), Some(&new), SeqCst, SeqCst) }
+                .is_ok();
+        drop(new);
+
+        // exactly one of the two exchanges succeeds
+        let racer_won = racer.join().unwrap();
+        assert!(won != racer_won);
+        let expected = if won { 2 } else { 1 };
+        assert_eq!(cell.read().map(|v| v.value), Some(expected));
+
+        drop(current);
+        drop(cell);
+        assert_eq!(drops.load(Ordering::Relaxed), 3);
+    });
+}
+
+#[test]
+fn weak_upgrade_while_write() {
+    loom::model(|| {
+        let first = Arc::new(1);
+        let second = Arc::new(2);
+        let weak = Arc::new(RcuWeak::new());
+        weak.write_arc(&first);
+
+        let reader = {
+            let weak = weak.clone();
+            thread::spawn(move || weak.upgrade().map(|v| *v))
+        };
+        weak.write_arc(&second);
+
+        let value = reader.join().unwrap();
+        assert!(value == Some(1) || value == Some(2));
+        assert_eq!(weak.upgrade().map(|v| *v), Some(2));
+        drop(weak);
+        assert_eq!(Arc::weak_count(&first), 0);
+        assert_eq!(Arc::weak_count(&second), 0);
+    });
+}
+
+#[test]
+fn weak_swap_retry() {
+    // the retry loop is unbounded, bound the preemptions to keep it finite
+    let mut model = loom::model::Builder::new();
+    model.preemption_bound = Some(2);
+    model.check(|| {
+        let cell = Arc::new(RcuCell::new(0usize));
+        let increment = |cell: &RcuCell<usize>| loop {
+            let cur = cell.read().unwrap();
+            if cell
+                .swap_if_arc_eq_weak(&cur, Some(Arc::new(*cur + 1)))
+                .is_ok()
+            {
+                break;
+            }
+            thread::yield_now();
+        };
+
+        let racer = {
+            let cell = cell.clone();
+            thread::spawn(move || increment(&cell))
+        };
+        increment(&cell);
+        racer.join().unwrap();
+        assert_eq!(cell.read().map(|v| *v), Some(2));
+    });
+}
@@ -0,0 +1,35 @@
+//! The panic free subset of the api, the build fails at link time if any of
+//! the wrapped calls could panic. Only checked with optimizations on, where
+//! the unreachable panic branches are removed.
+#![cfg(all(feature = "panic-free", not(debug_assertions)))]
+
+use no_panic::no_panic;
+use rcu_cell::{RcuCell, WouldBlock};
+use std::sync::Arc;
+
+#[no_panic]
+fn try_read(cell: &RcuCell<u64>) -> Result<Option<Arc<u64>>, WouldBlock> {
+    cell.try_read()
+}
+
+#[no_panic]
+fn try_set(
+    cell: &RcuCell<u64>,
+    data: Option<Arc<u64>>,
+) -> Result<Option<Arc<u64>>, Option<Arc<u64>>> {
+    cell.try_set(data)
+}
+
+#[no_panic]
+fn try_write(cell: &RcuCell<u64>, data: Arc<u64>) -> Result<Option<Arc<u64>>, Arc<u64>> {
+    cell.try_write(data)
+}
+
+#[test]
+fn panic_free() {
+    let cell = RcuCell::new(1);
+    assert_eq!(try_read(&cell).unwrap().map(|v| *v), Some(1));
+    assert_eq!(try_write(&cell, Arc::new(2)).unwrap().map(|v| *v), Some(1));
+    assert_eq!(try_set(&cell, None).unwrap().map(|v| *v), Some(2));
+    assert_eq!(try_read(&cell), Ok(None));
+}